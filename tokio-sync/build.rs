@@ -0,0 +1,40 @@
+use std::env;
+
+// Probes the compile target for atomic compare-and-swap support and emits
+// `tokio_no_atomic_cas` when it is missing, so the rest of the crate can
+// fall back to a critical-section emulation instead of `std::sync::atomic`
+// CAS operations that don't exist (or abort) on the target. This crate
+// requires `std` regardless of that fallback, so `NO_CAS_TARGETS` below can
+// only list hosted targets -- see its doc comment.
+fn main() {
+    let target = match env::var("TARGET") {
+        Ok(target) => target,
+        Err(_) => return,
+    };
+
+    let has_cas = has_atomic_cas(&target);
+
+    if !has_cas {
+        println!("cargo:rustc-cfg=tokio_no_atomic_cas");
+    }
+
+    // Don't warn about `tokio_no_atomic_cas` being an "unexpected cfg" when
+    // it is not emitted.
+    println!("cargo:rustc-check-cfg=cfg(tokio_no_atomic_cas)");
+}
+
+/// Targets that are known not to support atomic compare-and-swap.
+///
+/// This crate depends on `std` unconditionally (`CausalCell` and
+/// `AtomicWaker` both use it directly), so this list can only ever name
+/// *hosted* targets -- a `-none-` bare-metal target would fail to build
+/// here regardless of CAS support, for the unrelated reason that `std`
+/// doesn't exist on it at all. The targets below are hosted, single-core
+/// pre-ARMv6 cores, where `std::sync::atomic` still provides the types but
+/// `compare_exchange` and friends either don't exist or abort, since the
+/// `ldrex`/`strex` instructions CAS relies on weren't added until ARMv6.
+const NO_CAS_TARGETS: &[&str] = &["armv5te-unknown-linux-gnueabi", "armv5te-unknown-linux-musleabi"];
+
+fn has_atomic_cas(target: &str) -> bool {
+    !NO_CAS_TARGETS.contains(&target)
+}
@@ -0,0 +1,359 @@
+//! A version of `UnsafeCell` that is checked for causality violations under
+//! `cfg(test)` and the `checked` feature.
+//!
+//! `CausalCell` is used internally to hold state that is shared between
+//! threads without going through a `Mutex`. The `with` / `with_mut` methods
+//! are `unsafe` because the caller is responsible for upholding the usual
+//! aliasing rules: any number of concurrent shared accesses are fine, but a
+//! mutable access must not be concurrent with any other access. Getting
+//! that wrong is exactly the kind of bug this crate exists to avoid, so under
+//! `cfg(test)` or the `checked` feature we don't just trust the call site -- we record
+//! every access and panic if two of them aren't ordered by a happens-before
+//! relationship.
+//!
+//! A thread touching the cell on the strength of some external
+//! synchronization (a `Mutex`, a channel, `AtomicWaker`, ...) only actually
+//! has a happens-before edge with the access it's racing against if that
+//! synchronization is reflected here too: [`publish`] and [`observe`] let a
+//! synchronization primitive record its own release/acquire as a logical
+//! clock tick, so the primitive that hands `T` off between threads is
+//! expected to call `publish` right after its release and `observe` right
+//! after its acquire, before touching the cell. Accesses that aren't
+//! bridged by a `publish`/`observe` pair are assumed unordered, and a
+//! conflicting pair of those panics.
+//!
+//! Without `cfg(test)` or the `checked` feature, all of the bookkeeping compiles away and `CausalCell`
+//! is exactly as cheap as a bare `UnsafeCell`.
+//!
+//! ## The epoch is process-wide, not per-cell
+//!
+//! [`publish`]/[`observe`] share a single process-wide logical clock rather
+//! than one scoped to each `CausalCell`. That's a deliberate simplification
+//! -- the primitives that call them (a `Mutex`, `AtomicWaker`, ...) have no
+//! handle back to the particular cell(s) their release/acquire is guarding,
+//! so there's nowhere to plumb a per-cell clock through -- but it does mean
+//! an `observe` can pick up an epoch bumped by a totally unrelated
+//! `publish`, on a totally unrelated cell, and use it to wave through an
+//! access that was never actually ordered against anything. In other words,
+//! this checker can produce false negatives (a real race goes undetected)
+//! when two unrelated causal handoffs happen to interleave; it cannot
+//! produce false positives. That's an acceptable trade for a lint that's
+//! compiled out entirely outside of checked builds: it catches the common case
+//! (forgetting to call `publish`/`observe` around the one handoff that
+//! actually guards a given cell) without requiring every primitive to carry
+//! its own clock.
+//!
+//! [`publish`]: fn.publish.html
+//! [`observe`]: fn.observe.html
+
+#[cfg(not(any(test, feature = "checked")))]
+mod inner {
+    use std::cell::UnsafeCell;
+
+    /// A checked version of `UnsafeCell`.
+    pub(crate) struct CausalCell<T>(UnsafeCell<T>);
+
+    impl<T> CausalCell<T> {
+        pub(crate) fn new(data: T) -> CausalCell<T> {
+            CausalCell(UnsafeCell::new(data))
+        }
+
+        #[inline]
+        pub(crate) unsafe fn with<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            f(&*self.0.get())
+        }
+
+        #[inline]
+        pub(crate) unsafe fn with_mut<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            f(&mut *self.0.get())
+        }
+    }
+
+    /// No-op outside of checked builds; see the checked `inner` module.
+    #[inline]
+    pub(crate) fn publish() -> usize {
+        0
+    }
+
+    /// No-op outside of checked builds; see the checked `inner` module.
+    #[inline]
+    pub(crate) fn observe() {}
+}
+
+#[cfg(any(test, feature = "checked"))]
+mod inner {
+    use std::cell::{Cell, UnsafeCell};
+    use std::panic::Location;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+
+    /// Process-wide logical clock. Bumped by [`publish`], observed by
+    /// [`observe`]; the two together let a synchronization primitive turn
+    /// its real release/acquire into a happens-before edge that
+    /// `CausalCell` can see.
+    static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        // The highest epoch this thread has synchronized with, via
+        // `observe`. Accesses stamped with an older epoch are considered
+        // ordered-before anything this thread does next.
+        static OBSERVED: Cell<usize> = Cell::new(0);
+    }
+
+    /// Records a release: call this right after performing the atomic
+    /// store/RMW that hands a `CausalCell`-guarded value off to another
+    /// thread. Returns the epoch stamped on this release, for bookkeeping
+    /// by the caller if it needs it.
+    pub(crate) fn publish() -> usize {
+        // `AcqRel` so this also picks up (and orders after) any release
+        // this thread hasn't already observed, the same way a real
+        // release op on the handoff's own atomic would.
+        EPOCH.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    /// Records an acquire: call this right after the atomic load/RMW that
+    /// observed a release published by another thread, before touching
+    /// the value it guards.
+    pub(crate) fn observe() {
+        let epoch = EPOCH.load(Ordering::Acquire);
+        OBSERVED.with(|observed| {
+            if epoch > observed.get() {
+                observed.set(epoch);
+            }
+        });
+    }
+
+    /// A checked version of `UnsafeCell`.
+    ///
+    /// Every `with`/`with_mut` call records the access in `history` and
+    /// verifies that it is causally ordered after any prior access that it
+    /// conflicts with (two shared accesses never conflict; a mutable
+    /// access conflicts with everything). Ordering is decided by comparing
+    /// the epoch each access was stamped with against what the current
+    /// thread has since `observe`d -- see the module docs for how that
+    /// epoch gets there.
+    pub(crate) struct CausalCell<T> {
+        data: UnsafeCell<T>,
+        history: Mutex<Vec<Access>>,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Access {
+        thread: ThreadId,
+        epoch: usize,
+        exclusive: bool,
+        location: &'static Location<'static>,
+    }
+
+    impl<T> CausalCell<T> {
+        pub(crate) fn new(data: T) -> CausalCell<T> {
+            CausalCell {
+                data: UnsafeCell::new(data),
+                history: Mutex::new(Vec::new()),
+            }
+        }
+
+        #[track_caller]
+        #[inline]
+        pub(crate) unsafe fn with<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&T) -> R,
+        {
+            self.record_access(false);
+            f(&*self.data.get())
+        }
+
+        #[track_caller]
+        #[inline]
+        pub(crate) unsafe fn with_mut<F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            self.record_access(true);
+            f(&mut *self.data.get())
+        }
+
+        #[track_caller]
+        fn record_access(&self, exclusive: bool) {
+            let location = Location::caller();
+            let this_thread = std::thread::current().id();
+            let observed = OBSERVED.with(Cell::get);
+
+            let access = Access {
+                thread: this_thread,
+                epoch: EPOCH.load(Ordering::Acquire),
+                exclusive,
+                location,
+            };
+
+            let mut history = self.history.lock().unwrap();
+
+            for prior in history.iter() {
+                // Two accesses from the same thread are always causally
+                // ordered by program order.
+                if prior.thread == access.thread {
+                    continue;
+                }
+
+                // Two shared accesses never conflict, regardless of
+                // ordering.
+                if !prior.exclusive && !access.exclusive {
+                    continue;
+                }
+
+                // If this thread has `observe`d an epoch newer than the one
+                // `prior` was stamped with, a `publish` came after `prior`
+                // and some synchronization primitive already turned it into
+                // a happens-before edge with this access -- it's not a race.
+                if observed > prior.epoch {
+                    continue;
+                }
+
+                panic!(
+                    "CausalCell data race detected: access at {} is concurrent \
+                     with {} access at {} and there is no happens-before edge \
+                     between them (did the handoff forget to call \
+                     `causal_cell::publish`/`observe`?)",
+                    access.location,
+                    if prior.exclusive { "an exclusive" } else { "a shared" },
+                    prior.location,
+                );
+            }
+
+            history.push(access);
+        }
+    }
+}
+
+pub(crate) use self::inner::{observe, publish, CausalCell};
+
+#[cfg(test)]
+mod tests {
+    use super::{observe, publish, CausalCell};
+
+    #[test]
+    fn uncontended_access_never_panics() {
+        let cell = CausalCell::new(0);
+        unsafe {
+            cell.with_mut(|v| *v = 1);
+            cell.with(|v| assert_eq!(*v, 1));
+        }
+    }
+
+    #[test]
+    fn two_shared_accesses_from_different_threads_never_conflict() {
+        let cell = CausalCell::new(42);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| unsafe {
+                    cell.with(|v| assert_eq!(*v, 42));
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn publish_then_observe_orders_a_cross_thread_handoff() {
+        // A release (`publish`) followed by an acquire (`observe`) on
+        // another thread is exactly the happens-before edge `CausalCell`
+        // needs to consider the handoff ordered, so the exclusive access
+        // on one side and the shared access on the other must not panic.
+        let cell = CausalCell::new(0);
+
+        unsafe {
+            cell.with_mut(|v| *v = 7);
+        }
+        publish();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                observe();
+                unsafe {
+                    cell.with(|v| assert_eq!(*v, 7));
+                }
+            });
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "CausalCell data race detected")]
+    fn concurrent_access_without_a_happens_before_edge_panics() {
+        // Two threads touching the cell with no `publish`/`observe` pair
+        // bridging them have no happens-before relationship, even though
+        // one access happens to run to completion before the other starts
+        // in wall-clock time -- `CausalCell` only knows about ordering it
+        // was told about. The `publish()` before the mutable access shows
+        // that a release which happened *before* an access orders nothing
+        // after it, and the freshly spawned thread never `observe`s
+        // anything, so no happens-before edge exists, regardless of what
+        // other tests have done to the process-wide epoch counter.
+        let cell = CausalCell::new(0);
+        publish();
+
+        unsafe {
+            cell.with_mut(|v| *v = 1);
+        }
+
+        std::thread::scope(|scope| {
+            // Re-raise the spawned thread's own panic, so its message is
+            // what `should_panic` sees.
+            if let Err(panic) = scope
+                .spawn(|| unsafe {
+                    cell.with(|v| assert_eq!(*v, 1));
+                })
+                .join()
+            {
+                std::panic::resume_unwind(panic);
+            }
+        });
+    }
+
+    #[test]
+    fn an_unrelated_publish_can_mask_a_real_race_on_another_cell() {
+        // Demonstrates the false-negative boundary documented in the module
+        // docs: the epoch is process-wide, so a `publish`/`observe` pair
+        // bridging one cell's handoff can also paper over a genuinely
+        // unordered access to a completely unrelated cell, as long as the
+        // unrelated `publish` happens to land first. This is the
+        // counterpart to `concurrent_access_without_a_happens_before_edge_panics`
+        // above: same access pattern, except this time a bystander
+        // `publish`/`observe` pair is threaded through and the race goes
+        // unnoticed.
+        let unrelated = CausalCell::new(());
+        let cell = CausalCell::new(0);
+
+        unsafe {
+            cell.with_mut(|v| *v = 1);
+        }
+        // A `publish` belonging to `unrelated`'s own handoff, with nothing
+        // to do with `cell` at all.
+        publish();
+
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    // This thread only ever touches `unrelated`, but calling
+                    // `observe` here still bumps its process-wide high-water
+                    // mark, which is enough to make the access to `cell`
+                    // below look ordered even though nothing actually
+                    // ordered it.
+                    unsafe {
+                        unrelated.with(|_| {});
+                    }
+                    observe();
+                    unsafe {
+                        cell.with(|v| assert_eq!(*v, 1));
+                    }
+                })
+                .join()
+                .unwrap();
+        });
+    }
+}
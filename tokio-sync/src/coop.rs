@@ -0,0 +1,171 @@
+//! Cooperative task scheduling budget.
+//!
+//! Resource primitives (channels, locks, ...) are polled from inside a task
+//! that the executor scheduled to run. A task that is always ready --- for
+//! example one that sits in a tight loop reading from a channel that always
+//! has another item available --- can monopolize the executor's thread and
+//! starve every other task. To prevent that, the runtime hands each task a
+//! budget of operations when it is polled; resource primitives that want to
+//! participate decrement the budget on every completed operation and yield
+//! back to the executor once it is exhausted.
+use std::cell::Cell;
+use std::task::{Context, Poll};
+
+/// The initial budget assigned to a task each time the executor polls it.
+const INITIAL: usize = 128;
+
+thread_local! {
+    static BUDGET: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// Run `f` with a fresh cooperative budget for the duration of the call.
+///
+/// The executor calls this once around each `Future::poll`, so that every
+/// poll of a task starts with a full budget regardless of how much the
+/// previous poll consumed.
+///
+/// Nothing in this crate calls `budget` yet, since `tokio-sync` has no
+/// executor of its own -- it's meant to be called by the task harness in
+/// the runtime crate that drives `Future::poll`. `poll_proceed` below is
+/// still safe to call without it: with no budget active, it simply never
+/// throttles.
+pub(crate) fn budget<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    BUDGET.with(|cell| {
+        let prev = cell.get();
+        cell.set(Some(INITIAL));
+
+        let ret = f();
+
+        cell.set(prev);
+
+        ret
+    })
+}
+
+/// Poll whether the current task still has budget to perform another
+/// operation.
+///
+/// Resource primitives call this once per completed operation. If the
+/// budget is exhausted, this registers the current task to be woken again
+/// immediately and returns `Poll::Pending`, giving other tasks a chance to
+/// run before this one continues.
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    BUDGET.with(|cell| match cell.get() {
+        Some(0) => {
+            // Reset the budget here rather than leaving it at zero: without
+            // a `budget()` call wrapping every `Future::poll` to do this
+            // (see its doc comment), nothing else would ever give this
+            // thread's cooperative budget a fresh start, and every future
+            // call to `poll_proceed` -- on this task's next poll, or on any
+            // other task sharing the thread in the meantime -- would
+            // immediately hit this same branch forever instead of actually
+            // yielding for one tick and then resuming.
+            cell.set(Some(INITIAL));
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(remaining) => {
+            cell.set(Some(remaining - 1));
+            Poll::Ready(())
+        }
+        // No budget is active, e.g. because the caller isn't being driven by
+        // a runtime that participates in cooperative scheduling. Don't
+        // throttle in that case.
+        None => Poll::Ready(()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{budget, poll_proceed, INITIAL};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+        fn wake_by_ref(self: &Arc<Self>) {}
+    }
+
+    fn noop_cx() -> Context<'static> {
+        static WAKER: std::sync::OnceLock<Waker> = std::sync::OnceLock::new();
+        Context::from_waker(WAKER.get_or_init(|| Arc::new(NoopWaker).into()))
+    }
+
+    #[test]
+    fn poll_proceed_without_a_budget_never_throttles() {
+        let mut cx = noop_cx();
+        // No `budget()` call is active, so every call must be Ready, no
+        // matter how many times it's called.
+        for _ in 0..INITIAL * 2 {
+            assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+        }
+    }
+
+    #[test]
+    fn poll_proceed_yields_once_the_budget_is_exhausted() {
+        let mut cx = noop_cx();
+
+        budget(|| {
+            for _ in 0..INITIAL {
+                assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+            }
+            // The budget granted by `budget()` is now exhausted.
+            assert_eq!(poll_proceed(&mut cx), Poll::Pending);
+        });
+    }
+
+    #[test]
+    fn exhausting_the_budget_does_not_poison_later_polls() {
+        // Regression test: `poll_proceed` used to leave the thread-local
+        // budget at zero once exhausted, so every later call in the same
+        // `budget()` scope would spin on `Poll::Pending` instead of
+        // proceeding again after yielding once.
+        let mut cx = noop_cx();
+
+        budget(|| {
+            for _ in 0..INITIAL {
+                let _ = poll_proceed(&mut cx);
+            }
+            assert_eq!(poll_proceed(&mut cx), Poll::Pending);
+            // The yield reset the budget, so the next call proceeds.
+            assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+        });
+
+        budget(|| {
+            assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+        });
+    }
+
+    #[test]
+    fn nested_budgets_restore_the_outer_scope_on_exit() {
+        let mut cx = noop_cx();
+
+        budget(|| {
+            // Consume one unit of the outer budget before nesting.
+            assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+
+            budget(|| {
+                // The inner scope gets its own fresh budget, independent of
+                // the outer one's remaining count.
+                for _ in 0..INITIAL {
+                    assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+                }
+                assert_eq!(poll_proceed(&mut cx), Poll::Pending);
+            });
+
+            // Back in the outer scope, the budget should have continued
+            // counting down from where it left off (one unit used so far,
+            // `INITIAL - 1` left), not been reset or left exhausted by the
+            // inner scope.
+            for _ in 0..INITIAL - 1 {
+                assert_eq!(poll_proceed(&mut cx), Poll::Ready(()));
+            }
+            assert_eq!(poll_proceed(&mut cx), Poll::Pending);
+        });
+    }
+}
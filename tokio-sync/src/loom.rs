@@ -1,34 +1,21 @@
 pub(crate) mod futures {
-    pub use futures::task;
-    pub use ::atomic_task::AtomicTask;
+    pub use crate::task::AtomicWaker;
 }
 
 pub(crate) mod sync {
-    pub(crate) use std::sync::atomic;
+    pub(crate) use crate::causal_cell::CausalCell;
 
-    use std::cell::UnsafeCell;
-
-    pub struct CausalCell<T>(UnsafeCell<T>);
-
-    impl<T> CausalCell<T> {
-        pub fn new(data: T) -> CausalCell<T> {
-            CausalCell(UnsafeCell::new(data))
-        }
-
-        pub unsafe fn with<F, R>(&self, f: F) -> R
-        where
-            F: FnOnce(&T) -> R,
-        {
-            f(&*self.0.get())
-        }
-
-        pub unsafe fn with_mut<F, R>(&self, f: F) -> R
-        where
-            F: FnOnce(&mut T) -> R,
-        {
-            f(&mut *self.0.get())
-        }
+    #[cfg(not(tokio_no_atomic_cas))]
+    pub(crate) mod atomic {
+        pub(crate) use std::sync::atomic::{AtomicUsize, Ordering};
     }
-}
 
-pub fn yield_now() {}
\ No newline at end of file
+    // Targets without a native CAS instruction can't use `std::sync::atomic`'s
+    // `compare_exchange` directly (see `build.rs`); fall back to a
+    // critical-section emulation that exposes the same surface.
+    #[cfg(tokio_no_atomic_cas)]
+    pub(crate) mod atomic {
+        pub(crate) use crate::no_atomic_cas::AtomicUsizeCas as AtomicUsize;
+        pub(crate) use std::sync::atomic::Ordering;
+    }
+}
\ No newline at end of file
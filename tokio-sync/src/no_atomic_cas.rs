@@ -0,0 +1,167 @@
+//! A fallback for targets without atomic compare-and-swap.
+//!
+//! Some hosted, single-core targets (see `build.rs`) expose `AtomicUsize`
+//! loads and stores but have no CAS instruction at all -- pre-ARMv6 cores
+//! are the common case, since `ldrex`/`strex` only arrived with ARMv6 -- so
+//! `compare_exchange` either doesn't exist or panics at runtime. On those
+//! targets `cfg(tokio_no_atomic_cas)` is set and this module provides a
+//! drop-in replacement for the handful of atomic operations `CausalCell`
+//! and `AtomicWaker` need, emulated with a global critical section.
+//!
+//! Every target this crate can actually be built for has `std` available
+//! (`CausalCell` and `AtomicWaker` both depend on it unconditionally), so
+//! the critical section here is a plain `std::sync::Mutex` rather than a
+//! bare-metal interrupt-disable: there's no interrupt mask to twiddle from
+//! userspace on a hosted OS, and single-core-ness is what makes a
+//! process-wide mutex an adequate (if coarse) stand-in for CAS here, since
+//! there's never a second thread running concurrently on another core to
+//! contend with it.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A process-wide critical section used to emulate CAS on targets that
+/// don't have it natively.
+struct CriticalSection(Mutex<()>);
+
+impl CriticalSection {
+    fn enter(&self) -> Guard<'_> {
+        Guard(self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+struct Guard<'a>(std::sync::MutexGuard<'a, ()>);
+
+static CS: CriticalSection = CriticalSection(Mutex::new(()));
+
+/// A `compare_exchange`-compatible atomic `usize`, implemented with a
+/// critical section on targets with no native CAS instruction.
+pub(crate) struct AtomicUsizeCas {
+    inner: AtomicUsize,
+}
+
+impl AtomicUsizeCas {
+    pub(crate) const fn new(v: usize) -> AtomicUsizeCas {
+        AtomicUsizeCas {
+            inner: AtomicUsize::new(v),
+        }
+    }
+
+    pub(crate) fn load(&self, order: Ordering) -> usize {
+        self.inner.load(order)
+    }
+
+    pub(crate) fn store(&self, v: usize, order: Ordering) {
+        let _guard = CS.enter();
+        self.inner.store(v, order);
+    }
+
+    pub(crate) fn fetch_or(&self, v: usize, order: Ordering) -> usize {
+        let _guard = CS.enter();
+        self.inner.fetch_or(v, order)
+    }
+
+    pub(crate) fn fetch_and(&self, v: usize, order: Ordering) -> usize {
+        let _guard = CS.enter();
+        self.inner.fetch_and(v, order)
+    }
+
+    pub(crate) fn fetch_add(&self, v: usize, order: Ordering) -> usize {
+        let _guard = CS.enter();
+        self.inner.fetch_add(v, order)
+    }
+
+    pub(crate) fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        let _guard = CS.enter();
+        let actual = self.inner.load(success);
+
+        if actual == current {
+            self.inner.store(new, success);
+            Ok(actual)
+        } else {
+            let _ = failure;
+            Err(actual)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicUsizeCas;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+
+    #[test]
+    fn load_reflects_the_initial_value() {
+        let atomic = AtomicUsizeCas::new(7);
+        assert_eq!(atomic.load(SeqCst), 7);
+    }
+
+    #[test]
+    fn store_overwrites_the_value() {
+        let atomic = AtomicUsizeCas::new(0);
+        atomic.store(5, SeqCst);
+        assert_eq!(atomic.load(SeqCst), 5);
+    }
+
+    #[test]
+    fn fetch_or_and_fetch_and_update_bits_and_return_the_previous_value() {
+        let atomic = AtomicUsizeCas::new(0b1010);
+
+        assert_eq!(atomic.fetch_or(0b0101, SeqCst), 0b1010);
+        assert_eq!(atomic.load(SeqCst), 0b1111);
+
+        assert_eq!(atomic.fetch_and(0b1100, SeqCst), 0b1111);
+        assert_eq!(atomic.load(SeqCst), 0b1100);
+    }
+
+    #[test]
+    fn fetch_add_returns_the_previous_value() {
+        let atomic = AtomicUsizeCas::new(1);
+        assert_eq!(atomic.fetch_add(1, SeqCst), 1);
+        assert_eq!(atomic.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn compare_exchange_succeeds_when_the_current_value_matches() {
+        let atomic = AtomicUsizeCas::new(1);
+        assert_eq!(atomic.compare_exchange(1, 2, SeqCst, SeqCst), Ok(1));
+        assert_eq!(atomic.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn compare_exchange_fails_without_changing_the_value_on_mismatch() {
+        let atomic = AtomicUsizeCas::new(1);
+        assert_eq!(atomic.compare_exchange(0, 2, SeqCst, SeqCst), Err(1));
+        assert_eq!(atomic.load(SeqCst), 1);
+    }
+
+    #[test]
+    fn concurrent_compare_exchange_never_double_succeeds() {
+        // Exactly one of `N` concurrent `compare_exchange(0, 1, ..)` calls
+        // on a cell starting at `0` must succeed -- the critical section
+        // replacing CAS here has to provide the same exclusivity a real
+        // `compare_exchange` would.
+        let atomic = Arc::new(AtomicUsizeCas::new(0));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let atomic = atomic.clone();
+                std::thread::spawn(move || atomic.compare_exchange(0, 1, SeqCst, SeqCst).is_ok())
+            })
+            .collect();
+
+        let successes = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .filter(|succeeded| *succeeded)
+            .count();
+
+        assert_eq!(successes, 1);
+        assert_eq!(atomic.load(SeqCst), 1);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::loom::sync::atomic::AtomicUsize;
+use crate::loom::sync::atomic::Ordering::{AcqRel, Acquire, Release};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::task::Waker;
+
+/// A synchronization primitive for task notification.
+///
+/// `AtomicWaker` will coordinate concurrent wakes with the consumer
+/// potentially "waking" the underlying task. This is useful in cases where
+/// synchronization is required in tasks that are running on different
+/// threads.
+///
+/// `AtomicWaker` does not implement `Clone` on purpose, as this would lead
+/// to incorrect usage in 99% of cases. It is intended to be used by
+/// resources that allow only a single consumer to be registered at a time,
+/// e.g. a channel or a `Mutex`.
+pub struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// `AtomicWaker` is a lock-free cell holding at most one waker.
+// The state field is a single `AtomicUsize` value, but is a bit more
+// complicated than a simple spinlock due to the need to handle the case
+// where `register` and `wake` are called concurrently.
+//
+// The state field contains two bits: `REGISTERING` and `WAKING`. When
+// neither bit is set, the `AtomicWaker` is in the `WAITING` state.
+//
+//  * `WAITING` - the initial state. No registration and no wake is in
+//    progress; the cell may or may not hold a previously registered waker.
+//
+//  * `REGISTERING` - a thread is in the middle of storing a waker into the
+//    cell. This bit is acquired by `register` via a compare-and-swap on
+//    `WAITING -> REGISTERING`, which gives it exclusive access to the cell
+//    for the duration of the write.
+//
+//  * `WAKING` - a call to `wake` has happened, either before `register`
+//    observed it (in which case `register` must perform the wake itself) or
+//    concurrently with an in-progress `register` (in which case whoever is
+//    holding the `REGISTERING` bit is responsible for the wake once it is
+//    done writing).
+//
+// The `WAKING` bit may be set at any time via `fetch_or`. It is cleared
+// either by the `wake` call that set it (when it found the state `WAITING`)
+// or by the thread that is currently registering.
+const WAITING: usize = 0;
+const REGISTERING: usize = 0b01;
+const WAKING: usize = 0b10;
+
+impl AtomicWaker {
+    /// Create a new, empty `AtomicWaker`.
+    pub fn new() -> AtomicWaker {
+        AtomicWaker {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Registers the waker to be notified on calls to `wake`.
+    ///
+    /// The new waker will take place of any previous wakers that were
+    /// registered by previous calls to `register`. Any calls to `wake` that
+    /// happen after a call to `register` (as defined by the memory ordering
+    /// rules), will notify the `register` caller's latest registered waker.
+    pub fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Acquire, Acquire)
+            .unwrap_or_else(|x| x)
+        {
+            WAITING => {
+                unsafe {
+                    // Locked acquired, update the waker cell
+                    *self.waker.get() = Some(waker.clone());
+
+                    // Release the lock. If the state transitioned to include
+                    // the `WAKING` bit, this means a wake was called
+                    // concurrently, so we need to perform the wake
+                    // ourselves.
+                    let res = self
+                        .state
+                        .compare_exchange(REGISTERING, WAITING, AcqRel, Acquire);
+
+                    if let Err(actual) = res {
+                        // This branch can only be reached if a `wake`
+                        // happened while the waker was being registered, so
+                        // the `WAKING` bit must have been set.
+                        debug_assert_eq!(actual, REGISTERING | WAKING);
+
+                        // Take the waker to wake it below. Release the lock.
+                        let waker = (*self.waker.get()).take().unwrap();
+                        self.state.store(WAITING, Release);
+
+                        waker.wake();
+                    }
+                }
+            }
+            WAKING => {
+                // A wake is in progress. The waker being registered should
+                // just be woken up immediately, since the caller might be
+                // relying on its new waker being notified.
+                waker.wake_by_ref();
+            }
+            state => {
+                // In this case, a registration is in progress on another
+                // thread (`REGISTERING` is set, possibly with `WAKING` too).
+                // There is nothing to do other than let that registration
+                // finish; once it does, any pending `wake` will be handled
+                // by that thread.
+                debug_assert!(state == REGISTERING || state == REGISTERING | WAKING);
+            }
+        }
+    }
+
+    /// Notifies the task that last called `register`.
+    ///
+    /// If `register` has not been called yet, then this does nothing.
+    pub fn wake(&self) {
+        // `fetch_or` sets the `WAKING` bit, indicating to any in-progress
+        // `register` call that a wake happened, or reserving the right to
+        // perform the wake ourselves right now.
+        if self.state.fetch_or(WAKING, AcqRel) == WAITING {
+            // The state was `WAITING`, which means no registration is in
+            // progress, so we are responsible for waking the stored waker,
+            // if there is one.
+            let waker = unsafe { (*self.waker.get()).take() };
+
+            self.state.fetch_and(!WAKING, Release);
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        AtomicWaker::new()
+    }
+}
+
+impl fmt::Debug for AtomicWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AtomicWaker")
+    }
+}
+
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicWaker;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl CountingWaker {
+        fn new() -> Arc<CountingWaker> {
+            Arc::new(CountingWaker(AtomicUsize::new(0)))
+        }
+
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wake_with_no_registered_waker_does_nothing() {
+        let atomic_waker = AtomicWaker::new();
+        // Should not panic even though nothing has registered yet.
+        atomic_waker.wake();
+    }
+
+    #[test]
+    fn wake_after_register_notifies_the_registered_waker() {
+        let atomic_waker = AtomicWaker::new();
+        let counting_waker = CountingWaker::new();
+
+        atomic_waker.register(&counting_waker.clone().into());
+        atomic_waker.wake();
+
+        assert_eq!(counting_waker.count(), 1);
+    }
+
+    #[test]
+    fn register_after_a_completed_wake_with_nothing_registered_is_not_notified() {
+        // A `wake` with nothing registered does nothing: it clears the
+        // `WAKING` bit again before returning, so a later `register` just
+        // stores its waker for the next `wake`.
+        let atomic_waker = AtomicWaker::new();
+        let counting_waker = CountingWaker::new();
+
+        atomic_waker.wake();
+        atomic_waker.register(&counting_waker.clone().into());
+
+        assert_eq!(counting_waker.count(), 0);
+    }
+
+    #[test]
+    fn later_register_replaces_the_previously_registered_waker() {
+        let atomic_waker = AtomicWaker::new();
+        let first = CountingWaker::new();
+        let second = CountingWaker::new();
+
+        atomic_waker.register(&first.clone().into());
+        atomic_waker.register(&second.clone().into());
+        atomic_waker.wake();
+
+        assert_eq!(first.count(), 0);
+        assert_eq!(second.count(), 1);
+    }
+
+    #[test]
+    fn wake_after_register_completes_is_observed_on_the_next_register() {
+        // A wake that lands after `register` has already returned must
+        // still be visible to the *next* registration, which is the
+        // pattern a channel's receive loop relies on (register, check for
+        // work one more time, park).
+        let atomic_waker = AtomicWaker::new();
+        let first = CountingWaker::new();
+        let second = CountingWaker::new();
+
+        atomic_waker.register(&first.clone().into());
+        atomic_waker.wake();
+        assert_eq!(first.count(), 1);
+
+        // Registering again after the wake must not itself be swallowed by
+        // leftover state from the previous wake.
+        atomic_waker.register(&second.clone().into());
+        atomic_waker.wake();
+        assert_eq!(second.count(), 1);
+    }
+
+    #[test]
+    fn concurrent_register_and_wake_never_panics_or_deadlocks() {
+        // `register` and `wake` are designed to be called concurrently from
+        // different threads (a consumer registering interest, a producer
+        // notifying it) without requiring external synchronization between
+        // them. This doesn't assert exactly how many of the wakes land --
+        // a `wake` racing the very first `register` is legitimately allowed
+        // to find nothing registered yet -- only that hammering both from
+        // separate threads never panics or hangs.
+        let atomic_waker = Arc::new(AtomicWaker::new());
+        let counting_waker = CountingWaker::new();
+
+        let registering_thread = {
+            let atomic_waker = atomic_waker.clone();
+            let counting_waker = counting_waker.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    atomic_waker.register(&counting_waker.clone().into());
+                }
+            })
+        };
+
+        let waking_thread = {
+            let atomic_waker = atomic_waker.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    atomic_waker.wake();
+                }
+            })
+        };
+
+        registering_thread.join().unwrap();
+        waking_thread.join().unwrap();
+
+        // One final, unambiguous wake: nothing else is registering
+        // concurrently at this point, so it must be observed.
+        atomic_waker.register(&counting_waker.clone().into());
+        let count_before = counting_waker.count();
+        atomic_waker.wake();
+        assert_eq!(counting_waker.count(), count_before + 1);
+    }
+}
@@ -0,0 +1,5 @@
+mod atomic_waker;
+mod yield_now;
+
+pub use self::atomic_waker::AtomicWaker;
+pub use self::yield_now::yield_now;
@@ -0,0 +1,63 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Yields execution back to the runtime.
+///
+/// A task that calls this will be moved to the back of the scheduler's
+/// run queue, giving other tasks the chance to run. This is useful inside
+/// a loop that would otherwise keep completing ready work without ever
+/// returning `Poll::Pending`.
+pub async fn yield_now() {
+    /// Yield implementation
+    struct YieldNow {
+        yielded: bool,
+    }
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.yielded {
+                return Poll::Ready(());
+            }
+
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    YieldNow { yielded: false }.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::yield_now;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+        fn wake_by_ref(self: &Arc<Self>) {}
+    }
+
+    fn noop_cx() -> Context<'static> {
+        static WAKER: std::sync::OnceLock<Waker> = std::sync::OnceLock::new();
+        Context::from_waker(WAKER.get_or_init(|| Arc::new(NoopWaker).into()))
+    }
+
+    #[test]
+    fn yields_exactly_once_before_becoming_ready() {
+        let mut fut = Box::pin(yield_now());
+        let mut cx = noop_cx();
+
+        // The first poll must yield back to the executor...
+        assert_eq!(fut.as_mut().poll(&mut cx), Poll::Pending);
+        // ...and the second poll must complete, without yielding again.
+        assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+}
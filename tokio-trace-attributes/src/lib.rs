@@ -0,0 +1,228 @@
+//! A procedural macro attribute for automatically instrumenting functions
+//! with `tokio-trace` spans.
+//!
+//! This crate provides the `#[instrument]` attribute, a companion to the
+//! `tokio-trace` crate. The macro wraps a
+//! function body in a span (generated using `tokio-trace`'s existing
+//! `span!`/`callsite!` infrastructure), named after the function by
+//! default, with the function's arguments recorded as fields on the span.
+//!
+//! See the documentation for the [`#[instrument]`][instrument] attribute
+//! for details.
+//!
+//! [instrument]: attr.instrument.html
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Block, Ident, ItemFn, LitStr, Token};
+
+/// Instruments a function to create and enter a `tokio-trace` span every
+/// time the function is called.
+///
+/// The generated span's name defaults to the function's name. Each
+/// argument to the instrumented function is recorded as a field on the
+/// span using its `fmt::Debug` implementation, unless it is excluded via
+/// `skip(...)`.
+///
+/// For an `async fn`, the span is entered around each poll of the returned
+/// future (via [`Instrument`]), rather than just around the synchronous
+/// call that constructs it, so the span stays current across the
+/// function's `.await` points.
+///
+/// [`Instrument`]: ../tokio_trace/instrument/trait.Instrument.html
+///
+/// # Arguments
+///
+/// - `name = "..."` overrides the span's name (default: the function name).
+/// - `level = ...` overrides the span's level (default: `Level::DEBUG`).
+/// - `target = "..."` overrides the span's target (default: the enclosing
+///   module path).
+/// - `skip(arg, ...)` excludes the named arguments from the generated span,
+///   for arguments that are not `Debug` or are too large to record.
+/// - `fields(key = expr, ...)` records additional fields on the span that
+///   are not already present in the argument list.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate tokio_trace;
+/// # extern crate tokio_trace_attributes;
+/// use tokio_trace_attributes::instrument;
+///
+/// #[instrument]
+/// pub fn my_function(my_arg: usize) {
+///     // This event will be recorded inside a span named `my_function` with
+///     // the field `my_arg`.
+///     event!(tokio_trace::Level::INFO, "inside my_function!");
+/// }
+///
+/// #[instrument(level = "trace", skip(password))]
+/// pub fn login(username: &str, password: &str) -> bool {
+///     // ...
+/// # true
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn instrument(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as InstrumentArgs);
+    let input = parse_macro_input!(item as ItemFn);
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+        ..
+    } = input;
+
+    let span_name = args
+        .name
+        .unwrap_or_else(|| LitStr::new(&sig.ident.to_string(), Span::call_site()));
+    let level = args.level.unwrap_or_else(|| quote!(tokio_trace::Level::DEBUG));
+    let target = args
+        .target
+        .map(|t| quote!(#t))
+        .unwrap_or_else(|| quote!(module_path!()));
+
+    let param_names: Vec<Ident> = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(ident) => Some(ident.ident.clone()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .filter(|ident| !args.skips.contains(ident))
+        .collect();
+
+    let extra_fields = &args.fields;
+
+    let body = quote_spanned(&block);
+
+    // `Span::enter` only keeps the span current for the duration of a
+    // synchronous closure, which closes the span as soon as that closure
+    // returns -- for an `async fn`, that's as soon as the returned future is
+    // constructed, well before it's ever polled (and long before any
+    // `.await` inside it resolves). So instead of entering the span around
+    // the function body, wrap the body in its own `async move` block and
+    // instrument *that* future with the span, via `Instrument` (see
+    // `instrument.rs`), which re-enters the span around every poll.
+    let output = if sig.asyncness.is_some() {
+        quote! {
+            #(#attrs)* #vis #sig {
+                let __tokio_trace_attr_span = tokio_trace::span!(
+                    target: #target,
+                    #level,
+                    #span_name,
+                    #( #param_names = ?#param_names, )*
+                    #( #extra_fields, )*
+                );
+                tokio_trace::Instrument::instrument(async move #body, __tokio_trace_attr_span).await
+            }
+        }
+    } else {
+        quote! {
+            #(#attrs)* #vis #sig {
+                let __tokio_trace_attr_span = tokio_trace::span!(
+                    target: #target,
+                    #level,
+                    #span_name,
+                    #( #param_names = ?#param_names, )*
+                    #( #extra_fields, )*
+                );
+                __tokio_trace_attr_span.enter(|| #body)
+            }
+        }
+    };
+
+    output.into()
+}
+
+fn quote_spanned(block: &Block) -> proc_macro2::TokenStream {
+    quote!(#block)
+}
+
+/// The arguments accepted by `#[instrument(...)]`.
+#[derive(Default)]
+struct InstrumentArgs {
+    name: Option<LitStr>,
+    level: Option<proc_macro2::TokenStream>,
+    target: Option<LitStr>,
+    skips: Vec<Ident>,
+    fields: Vec<proc_macro2::TokenStream>,
+}
+
+mod kw {
+    syn::custom_keyword!(name);
+    syn::custom_keyword!(level);
+    syn::custom_keyword!(target);
+    syn::custom_keyword!(skip);
+    syn::custom_keyword!(fields);
+}
+
+impl Parse for InstrumentArgs {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut args = InstrumentArgs::default();
+
+        while !input.is_empty() {
+            let lookahead = input.lookahead1();
+
+            if lookahead.peek(kw::name) {
+                input.parse::<kw::name>()?;
+                input.parse::<Token![=]>()?;
+                args.name = Some(input.parse()?);
+            } else if lookahead.peek(kw::level) {
+                input.parse::<kw::level>()?;
+                input.parse::<Token![=]>()?;
+                args.level = Some(parse_level(input)?);
+            } else if lookahead.peek(kw::target) {
+                input.parse::<kw::target>()?;
+                input.parse::<Token![=]>()?;
+                args.target = Some(input.parse()?);
+            } else if lookahead.peek(kw::skip) {
+                input.parse::<kw::skip>()?;
+                let content;
+                syn::parenthesized!(content in input);
+                let idents = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                args.skips.extend(idents);
+            } else if lookahead.peek(kw::fields) {
+                input.parse::<kw::fields>()?;
+                let content;
+                syn::parenthesized!(content in input);
+                while !content.is_empty() {
+                    let field: Ident = content.parse()?;
+                    content.parse::<Token![=]>()?;
+                    let expr: syn::Expr = content.parse()?;
+                    args.fields.push(quote!(#field = #expr));
+                    if !content.is_empty() {
+                        content.parse::<Token![,]>()?;
+                    }
+                }
+            } else {
+                return Err(lookahead.error());
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+fn parse_level(input: ParseStream<'_>) -> syn::Result<proc_macro2::TokenStream> {
+    if let Ok(lit) = input.parse::<LitStr>() {
+        let level = Ident::new(&lit.value().to_uppercase(), lit.span());
+        return Ok(quote!(tokio_trace::Level::#level));
+    }
+
+    let expr: syn::Expr = input.parse()?;
+    Ok(quote!(#expr))
+}
@@ -0,0 +1,132 @@
+//! Integration tests for the `#[instrument]` attribute, run against a real
+//! `tokio-trace` dispatcher rather than just the macro's token output.
+//!
+//! This is a separate integration test (rather than a `#[cfg(test)] mod
+//! tests` inside the proc-macro crate itself) because exercising the
+//! *expansion* of `#[instrument]` requires a crate that actually depends on
+//! `tokio-trace` at runtime, which a proc-macro-only crate's own unit tests
+//! cannot do.
+//!
+//! Regression coverage for the bug fixed in `ccfa6d9`: for an `async fn`,
+//! the span must be entered around each poll of the *returned future* (via
+//! `Instrument`), not just around the synchronous call that constructs it
+//! --- getting this wrong closes the span before the function's body (and
+//! any `.await` points inside it) ever actually runs.
+#[macro_use]
+extern crate tokio_trace;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use tokio_trace::field::Field;
+use tokio_trace::{span, Metadata, Subscriber};
+use tokio_trace_attributes::instrument;
+
+#[derive(Clone, Default)]
+struct CountingSubscriber(Arc<Counts>);
+
+#[derive(Default)]
+struct Counts {
+    enters: AtomicUsize,
+    exits: AtomicUsize,
+}
+
+impl CountingSubscriber {
+    fn enters(&self) -> usize {
+        self.0.enters.load(Ordering::SeqCst)
+    }
+
+    fn exits(&self) -> usize {
+        self.0.exits.load(Ordering::SeqCst)
+    }
+}
+
+impl Subscriber for CountingSubscriber {
+    fn new_span(&self, _metadata: &Metadata<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+    fn record_debug(&self, _span: &span::Id, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    fn add_follows_from(&self, _span: &span::Id, _follows: span::Id) {}
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+    fn enter(&self, _span: &span::Id) {
+        self.0.enters.fetch_add(1, Ordering::SeqCst);
+    }
+    fn exit(&self, _span: &span::Id) {
+        self.0.exits.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        raw()
+    }
+    fn noop(_: *const ()) {}
+    fn raw() -> RawWaker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    unsafe { Waker::from_raw(raw()) }
+}
+
+#[instrument]
+fn sync_fn(x: usize) -> usize {
+    x + 1
+}
+
+#[instrument]
+async fn async_fn(x: usize) -> usize {
+    std::future::poll_fn(|cx| {
+        // Yield once before resolving, so the test can observe the span
+        // being exited at the pending point and re-entered on the next
+        // poll.
+        static POLLED: AtomicUsize = AtomicUsize::new(0);
+        if POLLED.fetch_add(1, Ordering::SeqCst) == 0 {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    })
+    .await;
+    x + 1
+}
+
+#[test]
+fn sync_fn_enters_and_exits_its_span_exactly_once() {
+    let subscriber = CountingSubscriber::default();
+    tokio_trace::subscriber::with_default(subscriber.clone(), || {
+        assert_eq!(sync_fn(41), 42);
+    });
+
+    assert_eq!(subscriber.enters(), 1);
+    assert_eq!(subscriber.exits(), 1);
+}
+
+#[test]
+fn async_fn_enters_and_exits_its_span_once_per_poll_across_await_points() {
+    let subscriber = CountingSubscriber::default();
+    tokio_trace::subscriber::with_default(subscriber.clone(), || {
+        let mut fut = Box::pin(async_fn(41));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // First poll hits the `.await` inside the function body and returns
+        // `Pending`; the span the attribute generated must already have
+        // been entered (and exited again on the way out) for this poll,
+        // proving it's driven via `Instrument` around the returned future
+        // rather than just around the synchronous call that built it.
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Pending);
+        assert_eq!(subscriber.enters(), 1);
+        assert_eq!(subscriber.exits(), 1);
+
+        // Second poll resolves the `.await` and completes the function; the
+        // same span is re-entered (and exited) exactly once more.
+        assert_eq!(Pin::new(&mut fut).poll(&mut cx), Poll::Ready(42));
+        assert_eq!(subscriber.enters(), 2);
+        assert_eq!(subscriber.exits(), 2);
+    });
+}
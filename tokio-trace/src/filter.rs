@@ -0,0 +1,344 @@
+//! A dynamic, per-target, per-level filter driven by a directive string,
+//! similar in spirit to `env_logger`'s `RUST_LOG` syntax.
+//!
+//! ```text
+//! info,my_crate::net=trace,my_crate::db[{conn_id}]=debug
+//! ```
+//!
+//! Each comma-separated directive sets the level for an optional target
+//! prefix (and, within that target, an optional span field name that must
+//! be present for the directive to apply); a directive with no target
+//! applies globally. When more than one directive matches a given
+//! callsite, the most specific one wins --- longer target prefixes beat
+//! shorter ones, and between equally long target prefixes a directive with
+//! a field predicate beats one without.
+use crate::field::Field;
+use crate::level_filters::LevelFilter;
+use crate::span;
+use crate::subscriber::{Interest, Subscriber};
+use crate::Metadata;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+/// The default name of the environment variable used to configure an
+/// [`EnvFilter`] via [`EnvFilter::from_default_env`].
+///
+/// [`EnvFilter`]: struct.EnvFilter.html
+/// [`EnvFilter::from_default_env`]: struct.EnvFilter.html#method.from_default_env
+pub const DEFAULT_ENV: &str = "RUST_LOG";
+
+/// A single parsed directive, e.g. `my_crate::net[{conn_id}]=debug`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    target: Option<String>,
+    field: Option<String>,
+    level: LevelFilter,
+}
+
+impl Directive {
+    /// The specificity of this directive, used to rank directives that
+    /// both match a given callsite: a longer target prefix is more
+    /// specific, and for equally long target prefixes a field predicate
+    /// breaks the tie.
+    fn specificity(&self) -> (usize, bool) {
+        (
+            self.target.as_ref().map_or(0, |t| t.len()),
+            self.field.is_some(),
+        )
+    }
+
+    fn matches_target(&self, target: &str) -> bool {
+        match &self.target {
+            // A directive's target is a path *prefix*, but matching must
+            // happen on whole `::`-delimited segments --- a raw
+            // `str::starts_with` would let a directive for `my_crate::net`
+            // also match the unrelated module `my_crate::network`.
+            Some(t) => {
+                let mut directive_segments = t.split("::");
+                let mut target_segments = target.split("::");
+                directive_segments.all(|d| target_segments.next() == Some(d))
+            }
+            None => true,
+        }
+    }
+
+    fn matches_fields(&self, fields: &[Field]) -> bool {
+        match &self.field {
+            Some(name) => fields.iter().any(|f| f.name() == name),
+            None => true,
+        }
+    }
+}
+
+impl FromStr for Directive {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `target[{field}]=level`, with everything but `level` optional.
+        let (spec, level) = match s.rfind('=') {
+            Some(pos) => (&s[..pos], s[pos + 1..].parse()?),
+            None => (s, LevelFilter::TRACE),
+        };
+
+        if spec.is_empty() {
+            return Ok(Directive {
+                target: None,
+                field: None,
+                level,
+            });
+        }
+
+        let (target, field) = match spec.find('[') {
+            Some(pos) => {
+                let target = &spec[..pos];
+                let rest = &spec[pos + 1..];
+                let end = rest
+                    .find(']')
+                    .ok_or_else(|| ParseError::new(format!("missing ']' in '{}'", s)))?;
+                let field = &rest[..end];
+                let field = field.trim_matches(|c| c == '{' || c == '}');
+                (
+                    if target.is_empty() { None } else { Some(target) },
+                    Some(field),
+                )
+            }
+            None => (Some(spec), None),
+        };
+
+        // Bare directives with no `=level` and no target at all (e.g. just
+        // `info`) set the global default level; a bare target with no
+        // level (e.g. `my_crate::net`) defaults to `TRACE` so that naming a
+        // target at all turns everything in it on.
+        let is_bare_level = field.is_none() && target.map_or(false, |t| t.parse::<LevelFilter>().is_ok());
+        if is_bare_level {
+            return Ok(Directive {
+                target: None,
+                field: None,
+                level: target.unwrap().parse()?,
+            });
+        }
+
+        Ok(Directive {
+            target: target.map(String::from),
+            field: field.map(String::from),
+            level,
+        })
+    }
+}
+
+impl FromStr for LevelFilter {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "OFF" => Ok(LevelFilter::OFF),
+            "ERROR" => Ok(LevelFilter::ERROR),
+            "WARN" => Ok(LevelFilter::WARN),
+            "INFO" => Ok(LevelFilter::INFO),
+            "DEBUG" => Ok(LevelFilter::DEBUG),
+            "TRACE" => Ok(LevelFilter::TRACE),
+            _ => Err(ParseError::new(format!("invalid level '{}'", s))),
+        }
+    }
+}
+
+/// An error encountered while parsing a directive string.
+#[derive(Debug)]
+pub struct ParseError(String);
+
+impl ParseError {
+    fn new(msg: impl Into<String>) -> Self {
+        ParseError(msg.into())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid filter directive: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A filter that enables or disables spans and events based on a set of
+/// directives parsed from a string, in the style of `env_logger`'s
+/// `RUST_LOG`.
+#[derive(Debug, Clone)]
+pub struct EnvFilter {
+    directives: Vec<Directive>,
+}
+
+impl EnvFilter {
+    /// Returns a new `EnvFilter` parsed from the given directive string.
+    pub fn new(directives: &str) -> Result<Self, ParseError> {
+        let directives = directives
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Directive>, _>>()?;
+
+        Ok(EnvFilter { directives })
+    }
+
+    /// Returns a new `EnvFilter` parsed from the value of the given
+    /// environment variable, or an empty filter (nothing enabled) if the
+    /// variable is unset.
+    pub fn from_env(var: &str) -> Result<Self, ParseError> {
+        match env::var(var) {
+            Ok(value) => EnvFilter::new(&value),
+            Err(_) => Ok(EnvFilter {
+                directives: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns a new `EnvFilter` parsed from the [`DEFAULT_ENV`]
+    /// (`RUST_LOG`) environment variable.
+    ///
+    /// [`DEFAULT_ENV`]: constant.DEFAULT_ENV.html
+    pub fn from_default_env() -> Result<Self, ParseError> {
+        EnvFilter::from_env(DEFAULT_ENV)
+    }
+
+    /// Determines the `Interest` this filter has in a callsite with the
+    /// given `Metadata`, based on the most specific matching directive.
+    pub fn interest(&self, metadata: &Metadata<'_>) -> Interest {
+        let fields: Vec<_> = metadata.fields().into_iter().collect();
+
+        let directive = self
+            .directives
+            .iter()
+            .filter(|d| d.matches_target(metadata.target()) && d.matches_fields(&fields))
+            .max_by_key(|d| d.specificity());
+
+        match directive {
+            Some(d) if d.level >= *metadata.level() => Interest::ALWAYS,
+            Some(_) => Interest::NEVER,
+            // No directive mentions this callsite at all: default to off,
+            // matching `RUST_LOG`'s "nothing is logged unless named".
+            None => Interest::NEVER,
+        }
+    }
+
+    /// Returns whether this filter enables the given `Metadata`.
+    pub fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.interest(metadata).is_always()
+    }
+}
+
+// `interest()`/`enabled()` above are just plain helper methods --- on their
+// own, nothing ever calls them. Implementing `Subscriber` is what lets an
+// `EnvFilter` be passed to `subscriber::with_default` and actually take part
+// in the callsite-interest pipeline described in the module docs, so a
+// `RUST_LOG` directive string can filter spans and events for real instead
+// of only being parseable.
+impl Subscriber for EnvFilter {
+    fn register_callsite(&self, metadata: &Metadata<'_>) -> Interest {
+        self.interest(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.enabled(metadata)
+    }
+
+    fn new_span(&self, _metadata: &Metadata<'_>) -> span::Id {
+        // `EnvFilter` only ever filters; it doesn't track span identity or
+        // state, so every span it's asked about gets the same placeholder
+        // id.
+        span::Id::from_u64(0)
+    }
+
+    fn record_debug(&self, _span: &span::Id, _field: &Field, _value: &dyn fmt::Debug) {}
+
+    fn add_follows_from(&self, _span: &span::Id, _follows: span::Id) {}
+
+    fn observe_event(&self, _event: &crate::Event<'_>) {}
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Directive;
+    use crate::level_filters::LevelFilter;
+
+    #[test]
+    fn bare_level_sets_the_global_default() {
+        let directive: Directive = "debug".parse().unwrap();
+        assert_eq!(directive.target, None);
+        assert_eq!(directive.field, None);
+        assert_eq!(directive.level, LevelFilter::DEBUG);
+    }
+
+    #[test]
+    fn bare_target_defaults_to_trace() {
+        // Naming a target at all, with no `=level`, turns everything in it
+        // on.
+        let directive: Directive = "my_crate::net".parse().unwrap();
+        assert_eq!(directive.target.as_deref(), Some("my_crate::net"));
+        assert_eq!(directive.field, None);
+        assert_eq!(directive.level, LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn target_with_explicit_level() {
+        let directive: Directive = "my_crate::net=warn".parse().unwrap();
+        assert_eq!(directive.target.as_deref(), Some("my_crate::net"));
+        assert_eq!(directive.field, None);
+        assert_eq!(directive.level, LevelFilter::WARN);
+    }
+
+    #[test]
+    fn target_with_field_and_level() {
+        let directive: Directive = "my_crate::db[{conn_id}]=debug".parse().unwrap();
+        assert_eq!(directive.target.as_deref(), Some("my_crate::db"));
+        assert_eq!(directive.field.as_deref(), Some("conn_id"));
+        assert_eq!(directive.level, LevelFilter::DEBUG);
+    }
+
+    #[test]
+    fn missing_closing_bracket_is_a_parse_error() {
+        assert!("my_crate::db[{conn_id}=debug".parse::<Directive>().is_err());
+    }
+
+    #[test]
+    fn invalid_level_is_a_parse_error() {
+        assert!("my_crate::net=noisy".parse::<Directive>().is_err());
+    }
+
+    #[test]
+    fn matches_target_respects_path_segment_boundaries() {
+        // The whole point of this fix: a directive for `my_crate::net` must
+        // not also match the unrelated, merely-prefixed module
+        // `my_crate::network`.
+        let directive: Directive = "my_crate::net".parse().unwrap();
+
+        assert!(directive.matches_target("my_crate::net"));
+        assert!(directive.matches_target("my_crate::net::tcp"));
+        assert!(!directive.matches_target("my_crate::network"));
+        assert!(!directive.matches_target("my_crate"));
+    }
+
+    #[test]
+    fn no_target_matches_everything() {
+        let directive: Directive = "debug".parse().unwrap();
+        assert!(directive.matches_target("anything::at::all"));
+    }
+
+    #[test]
+    fn longer_target_prefix_is_more_specific() {
+        let shallow: Directive = "my_crate=info".parse().unwrap();
+        let deep: Directive = "my_crate::net=trace".parse().unwrap();
+        assert!(deep.specificity() > shallow.specificity());
+    }
+
+    #[test]
+    fn a_field_predicate_is_more_specific_than_none() {
+        let without_field: Directive = "my_crate::net=debug".parse().unwrap();
+        let with_field: Directive = "my_crate::net[{conn_id}]=debug".parse().unwrap();
+        assert!(with_field.specificity() > without_field.specificity());
+    }
+}
@@ -0,0 +1,234 @@
+//! Attaching `Span`s to `Future`s.
+use crate::dispatcher::{self, Dispatch};
+use crate::span::Span;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Attaches spans to a `Future`.
+///
+/// Extension trait allowing any future to be instrumented with a `Span`.
+/// `Span::enter` only keeps the span current for the duration of a
+/// synchronous closure, which isn't enough for a future that may be polled,
+/// return `Pending`, and resume much later (potentially on a different
+/// thread) --- the span would already be exited by the time the next poll
+/// happens. `Instrument` fixes this by entering the span around each
+/// individual call to `poll`, so the span is current whenever the future is
+/// actually executing, and absent the rest of the time.
+pub trait Instrument: Sized {
+    /// Instruments this type with the provided `Span`, returning an
+    /// `Instrumented` wrapper.
+    ///
+    /// The attached span will be entered every time the instrumented type
+    /// is polled, and it stays open, across `Pending` returns, until the
+    /// `Instrumented` wrapper is dropped.
+    fn instrument(self, span: Span) -> Instrumented<Self> {
+        Instrumented { inner: self, span }
+    }
+
+    /// Instruments this type with the [current] `Span`, returning an
+    /// `Instrumented` wrapper.
+    ///
+    /// [current]: ../span/struct.Span.html#method.current
+    fn in_current_span(self) -> Instrumented<Self> {
+        self.instrument(Span::current())
+    }
+}
+
+impl<T: Sized> Instrument for T {}
+
+/// A future that has been instrumented with a `Span`.
+#[derive(Debug, Clone)]
+pub struct Instrumented<T> {
+    inner: T,
+    span: Span,
+}
+
+impl<T: Future> Future for Instrumented<T> {
+    type Output = T::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: `inner` is only ever projected to a `Pin<&mut T>`, and
+        // `span` is not structurally pinned, so this upholds the pin
+        // invariants of the wrapped future.
+        let this = unsafe { self.get_unchecked_mut() };
+        let span = &this.span;
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        // Entering around each individual poll (rather than once, at
+        // construction) is what keeps the span current across `.await`
+        // points: the span is only active while this future is actually
+        // being polled, and is exited as soon as `poll` returns, whether
+        // that's because the future is pending or complete.
+        span.enter(move || inner.poll(cx))
+    }
+}
+
+impl<T> Instrumented<T> {
+    /// Borrows the `Span` that this type is instrumented by.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Mutably borrows the `Span` that this type is instrumented by.
+    pub fn span_mut(&mut self) -> &mut Span {
+        &mut self.span
+    }
+
+    /// Borrows the wrapped type.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Mutably borrows the wrapped type.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consumes the `Instrumented`, returning the wrapped type.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl Span {
+    /// Returns a handle to the span that the current thread is currently
+    /// executing inside of, according to the current default `Dispatch`.
+    ///
+    /// This lets code that doesn't have the original `Span` value in scope
+    /// --- for example, a function spawning a task --- capture the caller's
+    /// span and propagate it, typically via [`Instrument`].
+    ///
+    /// If there is no current span, or no default subscriber is set, this
+    /// returns a disabled span, so it is always safe to call.
+    ///
+    /// [`Instrument`]: trait.Instrument.html
+    pub fn current() -> Span {
+        dispatcher::get_default(Span::capture)
+    }
+
+    fn capture(dispatch: &Dispatch) -> Span {
+        dispatch
+            .current_span()
+            .map(|id| Span::new_existing(dispatch.clone(), id))
+            .unwrap_or_else(Span::new_disabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Instrument;
+    use crate::span::Span;
+    use crate::{field::Field, span, Metadata, Subscriber};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    /// A `Subscriber` that just counts span enters/exits, so tests can
+    /// assert on the exact sequence `Instrumented::poll` drives it through.
+    #[derive(Clone, Default)]
+    struct CountingSubscriber(Arc<Counts>);
+
+    #[derive(Default)]
+    struct Counts {
+        enters: AtomicUsize,
+        exits: AtomicUsize,
+    }
+
+    impl CountingSubscriber {
+        fn enters(&self) -> usize {
+            self.0.enters.load(Ordering::SeqCst)
+        }
+
+        fn exits(&self) -> usize {
+            self.0.exits.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Subscriber for CountingSubscriber {
+        fn new_span(&self, _metadata: &Metadata<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+        fn record_debug(&self, _span: &span::Id, _field: &Field, _value: &dyn std::fmt::Debug) {}
+        fn add_follows_from(&self, _span: &span::Id, _follows: span::Id) {}
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn enter(&self, _span: &span::Id) {
+            self.0.enters.fetch_add(1, Ordering::SeqCst);
+        }
+        fn exit(&self, _span: &span::Id) {
+            self.0.exits.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            raw()
+        }
+        fn noop(_: *const ()) {}
+        fn raw() -> RawWaker {
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        unsafe { Waker::from_raw(raw()) }
+    }
+
+    /// A future that returns `Pending` the first `pending_polls` times it's
+    /// polled, then `Ready`.
+    struct PollN {
+        pending_polls: usize,
+    }
+
+    impl Future for PollN {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            if self.pending_polls == 0 {
+                Poll::Ready(())
+            } else {
+                self.pending_polls -= 1;
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn span_is_entered_and_exited_exactly_once_per_poll() {
+        let subscriber = CountingSubscriber::default();
+        crate::subscriber::with_default(subscriber.clone(), || {
+            let span = span!("test_span");
+            let mut fut = Box::pin(PollN { pending_polls: 2 }.instrument(span));
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+
+            assert_eq!(fut.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(subscriber.enters(), 1);
+            assert_eq!(subscriber.exits(), 1);
+
+            assert_eq!(fut.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(subscriber.enters(), 2);
+            assert_eq!(subscriber.exits(), 2);
+
+            // The span only actually closes (in the sense of the subscriber
+            // no longer being asked to re-enter it) once the future
+            // completes; up to that point each poll enters and exits it
+            // exactly once, never leaving it entered across the `.await`
+            // boundary represented by a `Pending` return.
+            assert_eq!(fut.as_mut().poll(&mut cx), Poll::Ready(()));
+            assert_eq!(subscriber.enters(), 3);
+            assert_eq!(subscriber.exits(), 3);
+        });
+    }
+
+    #[test]
+    fn span_current_is_disabled_with_no_active_context() {
+        // With no subscriber set as default (the test doesn't wrap this in
+        // `subscriber::with_default`), there's no active span context, so
+        // `Span::current` must fall back to a disabled span rather than
+        // panicking or fabricating one.
+        let span = Span::current();
+        assert!(span.is_disabled());
+    }
+}
@@ -0,0 +1,210 @@
+//! Trace verbosity filter, including the `STATIC_MAX_LEVEL` constant that
+//! lets a build strip every callsite above a chosen level at compile time.
+use crate::Level;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A filter comparable to a [`Level`], with an additional "off" state that
+/// disables every level.
+///
+/// `LevelFilter` and `Level` are distinct types so that a filter can express
+/// "nothing is enabled", which `Level` itself (every variant of which
+/// corresponds to an enabled level) cannot represent.
+///
+/// [`Level`]: struct.Level.html
+#[derive(Copy, Eq, PartialEq, Ord, PartialOrd, Clone, Hash)]
+pub struct LevelFilter(Option<Level>);
+
+impl LevelFilter {
+    /// The "off" level, disabling all tracing.
+    pub const OFF: LevelFilter = LevelFilter(None);
+    /// The "error" level.
+    pub const ERROR: LevelFilter = LevelFilter(Some(Level::ERROR));
+    /// The "warn" level.
+    pub const WARN: LevelFilter = LevelFilter(Some(Level::WARN));
+    /// The "info" level.
+    pub const INFO: LevelFilter = LevelFilter(Some(Level::INFO));
+    /// The "debug" level.
+    pub const DEBUG: LevelFilter = LevelFilter(Some(Level::DEBUG));
+    /// The "trace" level, enabling all levels.
+    pub const TRACE: LevelFilter = LevelFilter(Some(Level::TRACE));
+
+    /// Returns a `LevelFilter` that enables spans and events with the given
+    /// `Level`, and nothing above it.
+    pub const fn from_level(level: Level) -> Self {
+        LevelFilter(Some(level))
+    }
+
+    /// Returns the most verbose [`Level`] that this filter accepts, or
+    /// `None` if it is `OFF`.
+    pub const fn into_level(self) -> Option<Level> {
+        self.0
+    }
+}
+
+impl From<Level> for LevelFilter {
+    fn from(level: Level) -> Self {
+        LevelFilter::from_level(level)
+    }
+}
+
+impl From<Option<Level>> for LevelFilter {
+    fn from(level: Option<Level>) -> Self {
+        LevelFilter(level)
+    }
+}
+
+impl PartialEq<Level> for LevelFilter {
+    fn eq(&self, other: &Level) -> bool {
+        self.0.as_ref().map_or(false, |this| this == other)
+    }
+}
+
+impl PartialOrd<Level> for LevelFilter {
+    fn partial_cmp(&self, other: &Level) -> Option<Ordering> {
+        self.0.as_ref().map_or(Some(Ordering::Less), |this| this.partial_cmp(other))
+    }
+}
+
+impl fmt::Debug for LevelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(level) => fmt::Debug::fmt(&level, f),
+            None => f.pad("OFF"),
+        }
+    }
+}
+
+impl fmt::Display for LevelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(level) => fmt::Display::fmt(&level, f),
+            None => f.pad("off"),
+        }
+    }
+}
+
+// === compile-time max level ===
+//
+// `STATIC_MAX_LEVEL` is selected at compile time from the `max_level_*` /
+// `release_max_level_*` cargo features, in increasing order of
+// precedence: the `release_max_level_*` features only take effect in
+// `--release` builds, and take priority over the plain `max_level_*`
+// features there. With nothing selected, everything is enabled
+// (`STATIC_MAX_LEVEL = LevelFilter::TRACE`).
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_off"))]
+const MAX_LEVEL: LevelFilter = LevelFilter::OFF;
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_error", not(feature = "release_max_level_off")))]
+const MAX_LEVEL: LevelFilter = LevelFilter::ERROR;
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_warn", not(any(feature = "release_max_level_off", feature = "release_max_level_error"))))]
+const MAX_LEVEL: LevelFilter = LevelFilter::WARN;
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_info", not(any(feature = "release_max_level_off", feature = "release_max_level_error", feature = "release_max_level_warn"))))]
+const MAX_LEVEL: LevelFilter = LevelFilter::INFO;
+
+#[cfg(all(not(debug_assertions), feature = "release_max_level_debug", not(any(feature = "release_max_level_off", feature = "release_max_level_error", feature = "release_max_level_warn", feature = "release_max_level_info"))))]
+const MAX_LEVEL: LevelFilter = LevelFilter::DEBUG;
+
+#[cfg(not(any(
+    all(not(debug_assertions), feature = "release_max_level_off"),
+    all(not(debug_assertions), feature = "release_max_level_error"),
+    all(not(debug_assertions), feature = "release_max_level_warn"),
+    all(not(debug_assertions), feature = "release_max_level_info"),
+    all(not(debug_assertions), feature = "release_max_level_debug"),
+)))]
+const MAX_LEVEL: LevelFilter = {
+    #[cfg(feature = "max_level_off")]
+    {
+        LevelFilter::OFF
+    }
+    #[cfg(all(feature = "max_level_error", not(feature = "max_level_off")))]
+    {
+        LevelFilter::ERROR
+    }
+    #[cfg(all(feature = "max_level_warn", not(any(feature = "max_level_off", feature = "max_level_error"))))]
+    {
+        LevelFilter::WARN
+    }
+    #[cfg(all(feature = "max_level_info", not(any(feature = "max_level_off", feature = "max_level_error", feature = "max_level_warn"))))]
+    {
+        LevelFilter::INFO
+    }
+    #[cfg(all(feature = "max_level_debug", not(any(feature = "max_level_off", feature = "max_level_error", feature = "max_level_warn", feature = "max_level_info"))))]
+    {
+        LevelFilter::DEBUG
+    }
+    #[cfg(not(any(
+        feature = "max_level_off",
+        feature = "max_level_error",
+        feature = "max_level_warn",
+        feature = "max_level_info",
+        feature = "max_level_debug",
+    )))]
+    {
+        LevelFilter::TRACE
+    }
+};
+
+/// The statically configured maximum trace level.
+///
+/// Spans and events above this level are eliminated at compile time: the
+/// `event!`/`span!` macros emit `if STATIC_MAX_LEVEL >= $lvl` guards
+/// around their expansion, so for a disabled level the
+/// callsite registration and `Event::new`/`Span::new` construction are
+/// unreachable and the optimizer removes them entirely.
+pub const STATIC_MAX_LEVEL: LevelFilter = MAX_LEVEL;
+
+#[cfg(test)]
+mod tests {
+    use super::LevelFilter;
+    use crate::Level;
+
+    #[test]
+    fn off_is_less_than_every_level() {
+        assert!(LevelFilter::OFF < Level::ERROR);
+        assert!(LevelFilter::OFF < Level::TRACE);
+        assert_ne!(LevelFilter::OFF, Level::ERROR);
+    }
+
+    #[test]
+    fn a_filter_equals_the_level_it_was_built_from() {
+        assert_eq!(LevelFilter::from_level(Level::WARN), Level::WARN);
+        assert_ne!(LevelFilter::from_level(Level::WARN), Level::INFO);
+    }
+
+    #[test]
+    fn more_verbose_filters_compare_greater() {
+        // A filter set to a more verbose level lets more through, which is
+        // the ordering `span!`/`event!`'s `$lvl <= max` guards depend on.
+        assert!(LevelFilter::INFO >= Level::WARN);
+        assert!(LevelFilter::INFO < Level::DEBUG);
+        assert!(LevelFilter::TRACE >= Level::ERROR);
+        assert!(!(LevelFilter::OFF >= Level::ERROR));
+    }
+
+    #[test]
+    fn into_level_round_trips_through_from_level() {
+        assert_eq!(LevelFilter::from_level(Level::DEBUG).into_level(), Some(Level::DEBUG));
+        assert_eq!(LevelFilter::OFF.into_level(), None);
+    }
+
+    #[test]
+    fn display_and_debug_formatting() {
+        assert_eq!(LevelFilter::INFO.to_string(), Level::INFO.to_string());
+        assert_eq!(LevelFilter::OFF.to_string(), "off");
+        assert_eq!(format!("{:?}", LevelFilter::OFF), "OFF");
+        assert_eq!(format!("{:?}", LevelFilter::INFO), format!("{:?}", Level::INFO));
+    }
+
+    #[test]
+    fn levels_are_totally_ordered_from_error_to_trace() {
+        assert!(LevelFilter::ERROR < LevelFilter::WARN);
+        assert!(LevelFilter::WARN < LevelFilter::INFO);
+        assert!(LevelFilter::INFO < LevelFilter::DEBUG);
+        assert!(LevelFilter::DEBUG < LevelFilter::TRACE);
+        assert!(LevelFilter::OFF < LevelFilter::ERROR);
+    }
+}
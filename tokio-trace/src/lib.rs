@@ -236,6 +236,8 @@
 //! [`enabled`]: subscriber/trait.Subscriber.html#tymethod.enabled
 //! [metadata]: struct.Metadata.html
 extern crate tokio_trace_core;
+#[cfg(feature = "log")]
+extern crate log;
 
 // Somehow this `use` statement is necessary for us to re-export the `core`
 // macros on Rust 1.26.0. I'm not sure how this makes it work, but it does.
@@ -245,6 +247,7 @@ use tokio_trace_core::*;
 pub use self::{
     dispatcher::Dispatch,
     field::Value,
+    level_filters::LevelFilter,
     span::{Event, Id, Span},
     subscriber::Subscriber,
     tokio_trace_core::{
@@ -256,22 +259,28 @@ pub use self::{
 /// Constructs a new static callsite for a span or event.
 #[macro_export]
 macro_rules! callsite {
-    (span: $name:expr, $( $field_name:ident ),*) => ({
+    (span: $name:expr, $fields:expr) => (
+        callsite!(span: $crate::Level::TRACE, target: module_path!(), $name, $fields)
+    );
+    (span: $lvl:expr, $name:expr, $fields:expr) => (
+        callsite!(span: $lvl, target: module_path!(), $name, $fields)
+    );
+    (span: $lvl:expr, target: $target:expr, $name:expr, $fields:expr) => ({
         callsite!(@
             name: $name,
-            target: module_path!(),
-            level: $crate::Level::TRACE,
-            fields: &[ $(stringify!($field_name)),* ]
+            target: $target,
+            level: $lvl,
+            fields: $fields
         )
     });
-    (event: $lvl:expr, $( $field_name:ident ),*) =>
-        (callsite!(event: $lvl, target: module_path!(), $( $field_name ),* ));
-    (event: $lvl:expr, target: $target:expr, $( $field_name:ident ),*) => ({
+    (event: $lvl:expr, $fields:expr) =>
+        (callsite!(event: $lvl, target: module_path!(), $fields));
+    (event: $lvl:expr, target: $target:expr, $fields:expr) => ({
         callsite!(@
             name: concat!("event at ", file!(), ":", line!()),
             target: $target,
             level: $lvl,
-            fields: &[ "message", $(stringify!($field_name)),* ]
+            fields: $fields
         )
     });
     (@
@@ -339,6 +348,88 @@ macro_rules! callsite {
     })
 }
 
+/// Recursively walks a field list (`name`, `name = value`, `name = %value`,
+/// or `name = ?value`, comma-separated), shared by the `span!` and `event!`
+/// macros.
+///
+/// Declarative macros can't match a field list like `$( $k:ident $( =
+/// $val:tt )? ),*` directly, because an optional value that may itself span
+/// multiple tokens (as plain expressions do) creates a local ambiguity with
+/// the repetition's own comma separator. Instead, this macro consumes one
+/// field at a time from a raw token list, which lets each field's value be
+/// parsed as a full expression while still allowing a leading `%`/`?` sigil
+/// to be recognized before it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! valueset {
+    // Build up the list of field names for the callsite, as a `&'static
+    // [&'static str]`, ignoring the values entirely.
+    (@ names: [$($name:expr),*]; ) => ( &[ $($name),* ] );
+    (@ names: [$($name:expr),*]; $k:ident = % $val:expr, $($rest:tt)*) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; $($rest)*)
+    );
+    (@ names: [$($name:expr),*]; $k:ident = % $val:expr) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; )
+    );
+    (@ names: [$($name:expr),*]; $k:ident = ? $val:expr, $($rest:tt)*) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; $($rest)*)
+    );
+    (@ names: [$($name:expr),*]; $k:ident = ? $val:expr) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; )
+    );
+    (@ names: [$($name:expr),*]; $k:ident = $val:expr, $($rest:tt)*) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; $($rest)*)
+    );
+    (@ names: [$($name:expr),*]; $k:ident = $val:expr) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; )
+    );
+    (@ names: [$($name:expr),*]; $k:ident, $($rest:tt)*) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; $($rest)*)
+    );
+    (@ names: [$($name:expr),*]; $k:ident) => (
+        $crate::valueset!(@ names: [$($name,)* stringify!($k)]; )
+    );
+
+    // Record each field's value on `$target` (a `Span` or `Event`),
+    // consuming one key from `$keys` per field, in declaration order.
+    (@ record: $target:expr, $keys:expr; ) => ();
+    (@ record: $target:expr, $keys:expr; $k:ident = % $val:expr, $($rest:tt)*) => ({
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::display(&$val));
+        $crate::valueset!(@ record: $target, $keys; $($rest)*);
+    });
+    (@ record: $target:expr, $keys:expr; $k:ident = % $val:expr) => (
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::display(&$val));
+    );
+    (@ record: $target:expr, $keys:expr; $k:ident = ? $val:expr, $($rest:tt)*) => ({
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::debug(&$val));
+        $crate::valueset!(@ record: $target, $keys; $($rest)*);
+    });
+    (@ record: $target:expr, $keys:expr; $k:ident = ? $val:expr) => (
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::debug(&$val));
+    );
+    (@ record: $target:expr, $keys:expr; $k:ident = $val:expr, $($rest:tt)*) => ({
+        $crate::valueset!(@ record one: $target, $keys, $k, &$val);
+        $crate::valueset!(@ record: $target, $keys; $($rest)*);
+    });
+    (@ record: $target:expr, $keys:expr; $k:ident = $val:expr) => (
+        $crate::valueset!(@ record one: $target, $keys, $k, &$val);
+    );
+    // A bare field name with no value captures the same-named local
+    // variable, via `Debug`.
+    (@ record: $target:expr, $keys:expr; $k:ident, $($rest:tt)*) => ({
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::debug(&$k));
+        $crate::valueset!(@ record: $target, $keys; $($rest)*);
+    });
+    (@ record: $target:expr, $keys:expr; $k:ident) => (
+        $crate::valueset!(@ record one: $target, $keys, $k, &$crate::field::debug(&$k));
+    );
+    (@ record one: $target:expr, $keys:expr, $k:ident, $val:expr) => ({
+        let key = $keys.next()
+            .expect(concat!("metadata should define a key for '", stringify!($k), "'"));
+        $target.record(&key, $val);
+    });
+}
+
 /// Constructs a new span.
 ///
 /// # Examples
@@ -365,55 +456,183 @@ macro_rules! callsite {
 /// });
 /// # }
 /// ```
+///
+/// A span's level defaults to `Level::TRACE`, but may be overridden by
+/// giving an explicit level, written as a `Level::$variant` path, as the
+/// first argument. A `parent: span_id` argument overrides the span's
+/// parent, which otherwise defaults to the current span; `parent: None`
+/// instead constructs a root span, detached from the current span even if
+/// one is active. A `follows_from: span_id` argument records a causal,
+/// non-parent link to another span, via [`Subscriber::add_follows_from`].
+/// Field values may be prefixed with `%` or `?` to record them via their
+/// `Display` or `Debug` implementations, respectively, and a field with no
+/// value records the same-named local variable, via `Debug`:
+/// ```
+/// # #[macro_use]
+/// # extern crate tokio_trace;
+/// # fn main() {
+/// use tokio_trace::Level;
+/// let user = "ferris";
+/// span!(Level::INFO, "my span", user = %user, greeting = ?"hello").enter(|| {
+///     // do work inside the span...
+/// });
+/// # }
+/// ```
+///
+/// [`Subscriber::add_follows_from`]: subscriber/trait.Subscriber.html#tymethod.add_follows_from
 #[macro_export]
 macro_rules! span {
-    ($name:expr) => { span!($name,) };
-    ($name:expr, $($k:ident $( = $val:expr )* ) ,*) => {
-        {
+    // `target:` overrides the span's target, which otherwise defaults to
+    // the enclosing module path; it's only exposed via the internal `@
+    // span:` arm below, since it's meant for callers (like the
+    // `#[instrument]` attribute macro) that already know their target at
+    // expansion time, not for direct use in source.
+    (target: $target:expr, $lvl:expr, $name:expr, $($fields:tt)*) => {
+        span!(@ span: $lvl, $name, target: $target, parent: None, follows_from: None, fields: $($fields)*)
+    };
+    // An explicit level is always written as a `Level::$variant` path, and
+    // these arms match that literal `Level::` prefix rather than a generic
+    // `$lvl:expr`. A macro matcher can't otherwise tell "level, name" and
+    // "name, field" apart -- both are just two comma-separated expressions
+    // -- so without the literal prefix these arms and the bare, no-level
+    // arms below race for the same calls; matching `Level::` lets both
+    // coexist unambiguously, in arm order.
+    //
+    // `parent: None` explicitly detaches the new span from the current
+    // contextual span, constructing a root span even when one is active.
+    (Level::$lvl:ident, parent: None, $name:expr) => { span!(Level::$lvl, parent: None, $name,) };
+    (Level::$lvl:ident, parent: $parent:expr, $name:expr) => { span!(Level::$lvl, parent: $parent, $name,) };
+    (Level::$lvl:ident, follows_from: $follows:expr, $name:expr) => { span!(Level::$lvl, follows_from: $follows, $name,) };
+    (Level::$lvl:ident, parent: None, $name:expr, $($fields:tt)*) => {
+        span!(@ span: $crate::Level::$lvl, $name, target: module_path!(), parent: Some(None), follows_from: None, fields: $($fields)*)
+    };
+    (Level::$lvl:ident, parent: $parent:expr, $name:expr, $($fields:tt)*) => {
+        span!(@ span: $crate::Level::$lvl, $name, target: module_path!(), parent: Some(Some($parent)), follows_from: None, fields: $($fields)*)
+    };
+    (Level::$lvl:ident, follows_from: $follows:expr, $name:expr, $($fields:tt)*) => {
+        span!(@ span: $crate::Level::$lvl, $name, target: module_path!(), parent: None, follows_from: Some($follows), fields: $($fields)*)
+    };
+    (Level::$lvl:ident, $name:expr) => { span!(Level::$lvl, $name,) };
+    (Level::$lvl:ident, $name:expr, $($fields:tt)*) => {
+        span!(@ span: $crate::Level::$lvl, $name, target: module_path!(), parent: None, follows_from: None, fields: $($fields)*)
+    };
+    // Bare name, no level/parent/fields: defaults to `Level::TRACE`. These
+    // fall through to here only once none of the more specific arms above
+    // have matched, and they jump straight to the internal `@ span:` arm
+    // instead of recursing back into `span!` with a level prepended -- that
+    // recursion used to re-enter this same macro, land on this same
+    // catch-all arm again (an injected `$crate::Level::TRACE` is just
+    // another expression to match as the "name"), and recurse forever.
+    ($name:expr) => {
+        span!(@ span: $crate::Level::TRACE, $name, target: module_path!(), parent: None, follows_from: None, fields: )
+    };
+    ($name:expr, $($fields:tt)*) => {
+        span!(@ span: $crate::Level::TRACE, $name, target: module_path!(), parent: None, follows_from: None, fields: $($fields)*)
+    };
+    (@ span:
+        $lvl:expr,
+        $name:expr,
+        target: $target:expr,
+        parent: $parent:expr,
+        follows_from: $follows:expr,
+        fields: $($fields:tt)*
+    ) => {
+        // Both sides of this comparison are `const`, so when the span's
+        // level is above the statically configured max level, the compiler
+        // eliminates the callsite registration and `Span::new` expansion
+        // below as dead code, rather than merely skipping it at runtime.
+        if $crate::level_filters::STATIC_MAX_LEVEL >= $lvl {
             #[allow(unused_imports)]
             use $crate::{callsite, field::{Value, AsField}, Span};
             use $crate::callsite::Callsite;
-            let callsite = callsite! { span: $name, $( $k ),* };
-            let mut span = Span::new(callsite.interest(), callsite.metadata());
+            let callsite = callsite! {
+                span: $lvl, target: $target, $name, $crate::valueset!(@ names: []; $($fields)*)
+            };
+            // `None` means "no `parent:` clause was given, inherit the
+            // contextual span"; `Some(None)` means "`parent: None` was
+            // given explicitly, construct a root span"; `Some(Some(id))`
+            // means "`parent: id` was given, use it as the parent".
+            let mut span = match ($parent as Option<Option<$crate::span::Id>>) {
+                None => Span::new(callsite.interest(), callsite.metadata()),
+                Some(None) => Span::new_root(callsite.interest(), callsite.metadata()),
+                Some(Some(parent)) => Span::child_of(parent, callsite.interest(), callsite.metadata()),
+            };
             // Depending on how many fields are generated, this may or may
             // not actually be used, but it doesn't make sense to repeat it.
             #[allow(unused_variables, unused_mut)] {
                 if !span.is_disabled() {
+                    if let Some(from) = ($follows as Option<$crate::span::Id>) {
+                        span.follows_from(from);
+                    }
                     let mut keys = callsite.metadata().fields().into_iter();
-                    $(
-                        let key = keys.next()
-                            .expect(concat!("metadata should define a key for '", stringify!($k), "'"));
-                        span!(@ record: span, $k, &key, $($val)*);
-                    )*
+                    $crate::valueset!(@ record: span, keys; $($fields)*);
                 };
             }
             span
+        } else {
+            $crate::Span::new_disabled()
         }
     };
-    (@ record: $span:expr, $k:expr, $i:expr, $val:expr) => (
-        $span.record($i, &$val)
-    );
-    (@ record: $span:expr, $k:expr, $i:expr,) => (
-        // skip
-    );
 }
 
 #[macro_export]
 macro_rules! event {
-    // (target: $target:expr, $lvl:expr, { $( $k:ident $( = $val:expr )* ),* }, $fmt:expr ) => (
-    //     event!(target: $target, $lvl, { $($k $( = $val)* ),* }, $fmt, )
-    // );
-    (target: $target:expr, $lvl:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => ({
-        {
+    // `parent: None` explicitly detaches the event from the current
+    // contextual span, recording a root event even when one is active.
+    (target: $target:expr, parent: None, $lvl:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(@ $lvl, $target, parent: Some(None), { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, parent: None, $lvl:expr, { $($fields:tt)* } ) => (
+        event!(@ $lvl, $target, parent: Some(None), { $($fields)* })
+    );
+    (target: $target:expr, parent: $parent:expr, $lvl:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(@ $lvl, $target, parent: Some(Some($parent)), { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, parent: $parent:expr, $lvl:expr, { $($fields:tt)* } ) => (
+        event!(@ $lvl, $target, parent: Some(Some($parent)), { $($fields)* })
+    );
+    (target: $target:expr, $lvl:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(@ $lvl, $target, parent: None, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $lvl:expr, { $($fields:tt)* } ) => (
+        event!(@ $lvl, $target, parent: None, { $($fields)* })
+    );
+    ( $lvl:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: module_path!(), parent: $parent, $lvl, { $($fields)* }, $($arg)+)
+    );
+    ( $lvl:expr, parent: $parent:expr, $($arg:tt)+ ) => (
+        event!(target: module_path!(), parent: $parent, $lvl, { }, $($arg)+)
+    );
+    ( $lvl:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: module_path!(), $lvl, { $($fields)* }, $($arg)+)
+    );
+    ( $lvl:expr, $($arg:tt)+ ) => (
+        event!(target: module_path!(), $lvl, { }, $($arg)+)
+    );
+    // Shared implementation. `parent` is a `None`/`Some(None)`/`Some(Some(id))`
+    // expression with the same meaning as in `span!`: no clause given,
+    // explicit root, or explicit parent, respectively.
+    (@
+        $lvl:expr, $target:expr, parent: $parent:expr,
+        { $($fields:tt)* }, $($arg:tt)+
+    ) => ({
+        // Both sides of this comparison are `const`, so a level above the
+        // statically configured max level makes the whole callsite
+        // registration and `Event::new` expansion below dead code, which the
+        // optimizer removes entirely rather than merely skipping at runtime.
+        if $crate::level_filters::STATIC_MAX_LEVEL >= $lvl {
             #[allow(unused_imports)]
             use $crate::{callsite, Id, Subscriber, Event, field::{Value, AsField}};
             use $crate::callsite::Callsite;
-            let callsite = callsite! { event:
-                $lvl,
-                target:
-                $target, $( $k ),*
+            let callsite = callsite! {
+                event: $lvl, target: $target,
+                $crate::valueset!(@ names: ["message"]; $($fields)*)
+            };
+            let mut event = match ($parent as Option<Option<$crate::span::Id>>) {
+                None => Event::new(callsite.interest(), callsite.metadata()),
+                Some(None) => Event::new_root(callsite.interest(), callsite.metadata()),
+                Some(Some(parent)) => Event::child_of(parent, callsite.interest(), callsite.metadata()),
             };
-            let mut event = Event::new(callsite.interest(), callsite.metadata());
             // Depending on how many fields are generated, this may or may
             // not actually be used, but it doesn't make sense to repeat it.
             #[allow(unused_variables, unused_mut)] {
@@ -422,74 +641,115 @@ macro_rules! event {
                     let msg_key = keys.next()
                         .expect("event metadata should define a key for the message");
                     event.message(&msg_key, format_args!( $($arg)+ ));
-                    $(
-                        let key = keys.next()
-                            .expect(concat!("metadata should define a key for '", stringify!($k), "'"));
-                        event!(@ record: event, $k, &key, $($val)*);
-                    )*
+                    $crate::valueset!(@ record: event, keys; $($fields)*);
                 }
             }
             event
+        } else {
+            $crate::Event::new_disabled()
         }
     });
-    (target: $target:expr, $lvl:expr, { $( $k:ident $( = $val:expr )* ),* } ) => ({
-        {
+    (@
+        $lvl:expr, $target:expr, parent: $parent:expr,
+        { $($fields:tt)* }
+    ) => ({
+        if $crate::level_filters::STATIC_MAX_LEVEL >= $lvl {
             #[allow(unused_imports)]
             use $crate::{callsite, Id, Subscriber, Event, field::{Value, AsField}};
             use $crate::callsite::Callsite;
-            let callsite = callsite! { event:
-                $lvl,
-                target:
-                $target, $( $k ),*
+            let callsite = callsite! {
+                event: $lvl, target: $target,
+                $crate::valueset!(@ names: ["message"]; $($fields)*)
+            };
+            let mut event = match ($parent as Option<Option<$crate::span::Id>>) {
+                None => Event::new(callsite.interest(), callsite.metadata()),
+                Some(None) => Event::new_root(callsite.interest(), callsite.metadata()),
+                Some(Some(parent)) => Event::child_of(parent, callsite.interest(), callsite.metadata()),
             };
-            let mut event = Event::new(callsite.interest(), callsite.metadata());
-            // Depending on how many fields are generated, this may or may
-            // not actually be used, but it doesn't make sense to repeat it.
             #[allow(unused_variables, unused_mut)] {
                 if !event.is_disabled() {
                     let mut keys = callsite.metadata().fields().into_iter();
-                    let msg_key = keys.next()
+                    let _msg_key = keys.next()
                         .expect("event metadata should define a key for the message");
-                    $(
-                        let key = keys.next()
-                            .expect(concat!("metadata should define a key for '", stringify!($k), "'"));
-                        event!(@ record: event, $k, &key, $($val)*);
-                    )*
+                    $crate::valueset!(@ record: event, keys; $($fields)*);
                 }
             }
             event
+        } else {
+            $crate::Event::new_disabled()
         }
     });
-    ( $lvl:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: module_path!(), $lvl, { $($k $( = $val)* ),* }, $($arg)+)
-    );
-    ( $lvl:expr, $($arg:tt)+ ) => (
-        event!(target: module_path!(), $lvl, { }, $($arg)+)
-    );
-    (@ record: $ev:expr, $k:expr, $i:expr, $val:expr) => (
-        $ev.record($i, &$val);
-    );
-    (@ record: $ev:expr, $k:expr, $i:expr,) => (
-        // skip
-    );
 }
 
+// `trace!`/`debug!`/`info!`/`warn!`/`error!` are convenience wrappers around
+// `event!` for each `Level`. The `target: ...`/`parent: ...` forms, and the
+// braced field list followed by a message, forward their field list to
+// `event!` untouched, so `%`/`?` sigils are available there exactly as in
+// `event!` itself.
+//
+// The unbraced, message-less shorthand (`trace!(foo = %bar, baz)`) is
+// disambiguated from a plain message by its first token: a field list
+// always starts with a bare field name, while a message always starts with
+// a format string literal, so matching on a leading `$k:ident` tells the
+// two apart without needing to parse the field list itself. The rest of
+// the input is then captured as opaque `tt`s and spliced, untouched, into
+// the braced form below it -- exactly like the already-braced arms above
+// -- rather than re-parsed through a flat `$( $k:ident = $val:expr ),*`
+// pattern, which can't represent a `%`/`?`-sigilled value (`%val` isn't a
+// valid `expr`) without hitting the ambiguity `valueset!`'s doc comment
+// describes.
+
+/// Constructs an event at the `TRACE` level.
+///
+/// # Examples
+///
+/// The message form works exactly like `event!`; fields that accompany a
+/// message are written as a braced list before it:
+/// ```
+/// # #[macro_use]
+/// # extern crate tokio_trace;
+/// # fn main() {
+/// trace!("something happened");
+/// trace!({ foo = 2u64 }, "something happened with foo");
+/// # }
+/// ```
+///
+/// The message-less, fields-only shorthand accepts `%`/`?` sigils exactly
+/// like `event!`'s braced field list does, since it splices its raw input
+/// into that form untouched rather than re-parsing it:
+/// ```
+/// # #[macro_use]
+/// # extern crate tokio_trace;
+/// # fn main() {
+/// let user = "ferris";
+/// trace!(greeting = %user, payload = ?vec![1, 2, 3]);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! trace {
-    (target: $target:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: $target, $crate::Level::TRACE, { $($k $( = $val)* ),* }, $($arg)+)
+    (target: $target:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, parent: $parent, $crate::Level::TRACE, { $($fields)* }, $($arg)+)
     );
-    (target: $target:expr, $( $k:ident $( = $val:expr )* ),* ) => (
-        event!(target: $target, $crate::Level::TRACE, { $($k $( = $val)* ),* })
+    (target: $target:expr, parent: $parent:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, parent: $parent, $crate::Level::TRACE, { $k $($rest)* })
+    );
+    (target: $target:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, $crate::Level::TRACE, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, $crate::Level::TRACE, { $k $($rest)* })
     );
     (target: $target:expr, $($arg:tt)+ ) => (
         drop(event!(target: $target, $crate::Level::TRACE, {}, $($arg)+));
     );
-    ({ $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        trace!(target: module_path!(), { $($k $( = $val)* ),* }, $($arg)+)
+    (parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        trace!(target: module_path!(), parent: $parent, { $($fields)* }, $($arg)+)
+    );
+    ({ $($fields:tt)* }, $($arg:tt)+ ) => (
+        trace!(target: module_path!(), { $($fields)* }, $($arg)+)
     );
-    ($( $k:ident $( = $val:expr )* ),* ) => (
-        trace!(target: module_path!(), { $($k $( = $val)* ),* })
+    ($k:ident $($rest:tt)*) => (
+        event!(target: module_path!(), $crate::Level::TRACE, { $k $($rest)* })
     );
     ($($arg:tt)+ ) => (
         trace!(target: module_path!(), {}, $($arg)+)
@@ -498,20 +758,29 @@ macro_rules! trace {
 
 #[macro_export]
 macro_rules! debug {
-    (target: $target:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: $target, $crate::Level::DEBUG, { $($k $( = $val)* ),* }, $($arg)+)
+    (target: $target:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, parent: $parent, $crate::Level::DEBUG, { $($fields)* }, $($arg)+)
     );
-    (target: $target:expr, $( $k:ident $( = $val:expr )* ),* ) => (
-        event!(target: $target, $crate::Level::DEBUG, { $($k $( = $val)* ),* })
+    (target: $target:expr, parent: $parent:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, parent: $parent, $crate::Level::DEBUG, { $k $($rest)* })
+    );
+    (target: $target:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, $crate::Level::DEBUG, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, $crate::Level::DEBUG, { $k $($rest)* })
     );
     (target: $target:expr, $($arg:tt)+ ) => (
         drop(event!(target: $target, $crate::Level::DEBUG, {}, $($arg)+));
     );
-    ({ $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        debug!(target: module_path!(), { $($k $( = $val)* ),* }, $($arg)+)
+    (parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        debug!(target: module_path!(), parent: $parent, { $($fields)* }, $($arg)+)
     );
-    ($( $k:ident $( = $val:expr )* ),* ) => (
-        debug!(target: module_path!(), { $($k $( = $val)* ),* })
+    ({ $($fields:tt)* }, $($arg:tt)+ ) => (
+        debug!(target: module_path!(), { $($fields)* }, $($arg)+)
+    );
+    ($k:ident $($rest:tt)*) => (
+        event!(target: module_path!(), $crate::Level::DEBUG, { $k $($rest)* })
     );
     ($($arg:tt)+ ) => (
         debug!(target: module_path!(), {}, $($arg)+)
@@ -520,20 +789,29 @@ macro_rules! debug {
 
 #[macro_export]
 macro_rules! info {
-    (target: $target:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: $target, $crate::Level::INFO, { $($k $( = $val)* ),* }, $($arg)+)
+    (target: $target:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, parent: $parent, $crate::Level::INFO, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, parent: $parent:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, parent: $parent, $crate::Level::INFO, { $k $($rest)* })
     );
-    (target: $target:expr, $( $k:ident $( = $val:expr )* ),* ) => (
-        event!(target: $target, $crate::Level::INFO, { $($k $( = $val)* ),* })
+    (target: $target:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, $crate::Level::INFO, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, $crate::Level::INFO, { $k $($rest)* })
     );
     (target: $target:expr, $($arg:tt)+ ) => (
         drop(event!(target: $target, $crate::Level::INFO, {}, $($arg)+));
     );
-    ({ $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        info!(target: module_path!(), { $($k $( = $val)* ),* }, $($arg)+)
+    (parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        info!(target: module_path!(), parent: $parent, { $($fields)* }, $($arg)+)
+    );
+    ({ $($fields:tt)* }, $($arg:tt)+ ) => (
+        info!(target: module_path!(), { $($fields)* }, $($arg)+)
     );
-    ($( $k:ident $( = $val:expr )* ),* ) => (
-        info!(target: module_path!(), { $($k $( = $val)* ),* })
+    ($k:ident $($rest:tt)*) => (
+        event!(target: module_path!(), $crate::Level::INFO, { $k $($rest)* })
     );
     ($($arg:tt)+ ) => (
         info!(target: module_path!(), {}, $($arg)+)
@@ -542,20 +820,29 @@ macro_rules! info {
 
 #[macro_export]
 macro_rules! warn {
-    (target: $target:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: $target, $crate::Level::WARN, { $($k $( = $val)* ),* }, $($arg)+)
+    (target: $target:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, parent: $parent, $crate::Level::WARN, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, parent: $parent:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, parent: $parent, $crate::Level::WARN, { $k $($rest)* })
     );
-    (target: $target:expr, $( $k:ident $( = $val:expr )* ),* ) => (
-        event!(target: $target, $crate::Level::WARN, { $($k $( = $val)* ),* })
+    (target: $target:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, $crate::Level::WARN, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, $crate::Level::WARN, { $k $($rest)* })
     );
     (target: $target:expr, $($arg:tt)+ ) => (
         drop(event!(target: $target, $crate::Level::WARN, {}, $($arg)+));
     );
-    ({ $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        warn!(target: module_path!(), { $($k $( = $val)* ),* }, $($arg)+)
+    (parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        warn!(target: module_path!(), parent: $parent, { $($fields)* }, $($arg)+)
+    );
+    ({ $($fields:tt)* }, $($arg:tt)+ ) => (
+        warn!(target: module_path!(), { $($fields)* }, $($arg)+)
     );
-    ($( $k:ident $( = $val:expr )* ),* ) => (
-        warn!(target: module_path!(), { $($k $( = $val)* ),* })
+    ($k:ident $($rest:tt)*) => (
+        event!(target: module_path!(), $crate::Level::WARN, { $k $($rest)* })
     );
     ($($arg:tt)+ ) => (
         warn!(target: module_path!(), {}, $($arg)+)
@@ -564,20 +851,29 @@ macro_rules! warn {
 
 #[macro_export]
 macro_rules! error {
-    (target: $target:expr, { $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        event!(target: $target, $crate::Level::ERROR, { $($k $( = $val)* ),* }, $($arg)+)
+    (target: $target:expr, parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, parent: $parent, $crate::Level::ERROR, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, parent: $parent:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, parent: $parent, $crate::Level::ERROR, { $k $($rest)* })
     );
-    (target: $target:expr, $( $k:ident $( = $val:expr )* ),* ) => (
-        event!(target: $target, $crate::Level::ERROR, { $($k $( = $val)* ),* })
+    (target: $target:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        event!(target: $target, $crate::Level::ERROR, { $($fields)* }, $($arg)+)
+    );
+    (target: $target:expr, $k:ident $($rest:tt)*) => (
+        event!(target: $target, $crate::Level::ERROR, { $k $($rest)* })
     );
     (target: $target:expr, $($arg:tt)+ ) => (
         drop(event!(target: $target, $crate::Level::ERROR, {}, $($arg)+));
     );
-    ({ $( $k:ident $( = $val:expr )* ),* }, $($arg:tt)+ ) => (
-        error!(target: module_path!(), { $($k $( = $val)* ),* }, $($arg)+)
+    (parent: $parent:expr, { $($fields:tt)* }, $($arg:tt)+ ) => (
+        error!(target: module_path!(), parent: $parent, { $($fields)* }, $($arg)+)
+    );
+    ({ $($fields:tt)* }, $($arg:tt)+ ) => (
+        error!(target: module_path!(), { $($fields)* }, $($arg)+)
     );
-    ($( $k:ident $( = $val:expr )* ),* ) => (
-        error!(target: module_path!(), { $($k $( = $val)* ),* })
+    ($k:ident $($rest:tt)*) => (
+        event!(target: module_path!(), $crate::Level::ERROR, { $k $($rest)* })
     );
     ($($arg:tt)+ ) => (
         error!(target: module_path!(), {}, $($arg)+)
@@ -586,9 +882,17 @@ macro_rules! error {
 
 pub mod dispatcher;
 pub mod field;
+pub mod filter;
+pub mod instrument;
+pub mod level_filters;
+#[cfg(feature = "log")]
+pub mod log;
 pub mod span;
 pub mod subscriber;
 
+pub use self::filter::EnvFilter;
+pub use self::instrument::Instrument;
+
 mod sealed {
     pub trait Sealed {}
 }
@@ -0,0 +1,267 @@
+//! Compatibility with the [`log`] crate, so a project can migrate to
+//! `tokio-trace` without losing (or duplicating) diagnostics emitted
+//! through `log`.
+//!
+//! This module is only available when the `log` feature is enabled, since
+//! it adds an optional dependency on the `log` crate.
+//!
+//! Interop goes both ways:
+//!
+//! - [`AsLog`] converts a `tokio-trace` [`Level`] into a [`log::Level`], for
+//!   subscribers that want to re-emit events through `log` so that existing
+//!   `log`-based tooling keeps seeing them.
+//! - [`LogTracer`] is a [`log::Log`] implementation that converts incoming
+//!   `log::Record`s into `tokio-trace` [`Event`]s and dispatches them
+//!   through the current default [`Subscriber`], so libraries that still
+//!   call `log::info!`/etc. show up in the trace tree.
+//!
+//! [`log`]: https://docs.rs/log
+//! [`Level`]: ../struct.Level.html
+//! [`log::Level`]: https://docs.rs/log/latest/log/enum.Level.html
+//! [`Event`]: ../struct.Event.html
+//! [`Subscriber`]: ../subscriber/trait.Subscriber.html
+use crate::{Event, Level};
+use std::fmt;
+
+/// Converts a `tokio-trace` [`Level`] into the equivalent [`log::Level`].
+///
+/// [`Level`]: ../struct.Level.html
+/// [`log::Level`]: https://docs.rs/log/latest/log/enum.Level.html
+pub trait AsLog {
+    /// Returns the `log::Level` most closely corresponding to `self`.
+    fn as_log(&self) -> log::Level;
+}
+
+impl AsLog for Level {
+    fn as_log(&self) -> log::Level {
+        match *self {
+            Level::ERROR => log::Level::Error,
+            Level::WARN => log::Level::Warn,
+            Level::INFO => log::Level::Info,
+            Level::DEBUG => log::Level::Debug,
+            Level::TRACE => log::Level::Trace,
+        }
+    }
+}
+
+/// Converts a [`log::Level`] into the closest `tokio-trace` [`Level`].
+///
+/// [`log::Level`]: https://docs.rs/log/latest/log/enum.Level.html
+/// [`Level`]: ../struct.Level.html
+pub trait AsTrace {
+    /// Returns the `tokio-trace` `Level` most closely corresponding to
+    /// `self`.
+    fn as_trace(&self) -> Level;
+}
+
+impl AsTrace for log::Level {
+    fn as_trace(&self) -> Level {
+        match *self {
+            log::Level::Error => Level::ERROR,
+            log::Level::Warn => Level::WARN,
+            log::Level::Info => Level::INFO,
+            log::Level::Debug => Level::DEBUG,
+            log::Level::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// Formats an `Event`'s fields the way `log::Record`'s `args` expects them:
+/// the message field rendered as-is, with any remaining fields appended as
+/// `key=value` pairs.
+struct LogVisitor<'a>(&'a Event<'a>);
+
+impl fmt::Display for LogVisitor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.message())?;
+        for field in self.0.fields() {
+            if field.name() == "message" {
+                continue;
+            }
+            write!(f, " {}={:?}", field.name(), self.0.field_value(field))?;
+        }
+        Ok(())
+    }
+}
+
+/// Record a `tokio-trace` `Event` through the `log` crate's global logger,
+/// as a `log::Record` at the equivalent level.
+///
+/// Subscribers that want their events to also reach `log`-based tooling
+/// (e.g. because the binary configures both) can call this from
+/// `Subscriber::observe_event`.
+pub fn log_event(event: &Event<'_>) {
+    let metadata = event.metadata();
+    let level = metadata.level().as_log();
+
+    log::logger().log(
+        &log::Record::builder()
+            .args(format_args!("{}", LogVisitor(event)))
+            .level(level)
+            .target(metadata.target())
+            .module_path(metadata.module_path())
+            .file(metadata.file())
+            .line(metadata.line())
+            .build(),
+    );
+}
+
+/// A [`log::Log`] implementation that converts `log::Record`s into
+/// `tokio-trace` `Event`s, dispatched through the current default
+/// `Subscriber`.
+///
+/// Installing `LogTracer` as the global logger (via
+/// `log::set_boxed_logger`) lets libraries that still use `log::info!` and
+/// friends show up in the trace tree, with the record's target and level
+/// carried over into the event's metadata.
+///
+/// [`log::Log`]: https://docs.rs/log/latest/log/trait.Log.html
+#[derive(Debug, Default)]
+pub struct LogTracer {
+    _priv: (),
+}
+
+impl LogTracer {
+    /// Returns a new `LogTracer` that dispatches all `log` records.
+    pub fn new() -> Self {
+        LogTracer { _priv: () }
+    }
+}
+
+impl log::Log for LogTracer {
+    fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+        // Filtering is the current `Subscriber`'s job: `event!` below
+        // consults callsite interest the same way a `trace!` invoked
+        // directly would.
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        // `event!`/`callsite!` bake their target and level into a `static
+        // Metadata<'static>`, built once and registered for the lifetime of
+        // the program -- that's fine when both are literals known at the
+        // call site, but a `log::Record`'s target and level are runtime
+        // values that differ on every call. So instead of going through
+        // those macros, build the `Metadata`/`Event` by hand here, the way
+        // any other `log` interop shim has to.
+        use crate::{callsite::Callsite, subscriber::Interest, Event, Metadata};
+
+        /// The callsite identity shared by every bridged `log::Record`.
+        ///
+        /// A real callsite's interest is cached and only gets recomputed
+        /// when a filter changes, which assumes the callsite's metadata
+        /// (target, level, ...) is fixed -- not true here, where it's
+        /// different on every record. So this callsite always reports
+        /// [`Interest::SOMETIMES`], which tells the dispatcher to ask the
+        /// subscriber to decide fresh each time rather than caching a
+        /// verdict, and it is never passed to `callsite::register`, so the
+        /// global registry never calls back into `metadata()` below
+        /// expecting a fixed answer.
+        struct LogCallsite;
+
+        impl Callsite for LogCallsite {
+            fn add_interest(&self, _interest: Interest) {}
+            fn remove_interest(&self) {}
+            fn metadata(&self) -> &Metadata<'_> {
+                unreachable!("LogCallsite is never registered, so its metadata is never queried")
+            }
+        }
+
+        static LOG_CALLSITE: LogCallsite = LogCallsite;
+
+        let metadata = metadata! {
+            name: "log event",
+            target: record.target(),
+            level: record.level().as_trace(),
+            fields: &["message"],
+            callsite: &LOG_CALLSITE,
+        };
+
+        let mut event = Event::new(Interest::SOMETIMES, &metadata);
+        if !event.is_disabled() {
+            let mut keys = metadata.fields().into_iter();
+            let msg_key = keys
+                .next()
+                .expect("event metadata should define a key for the message");
+            event.message(&msg_key, format_args!("{}", record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogTracer;
+    use crate::{field::Field, span, Level, Metadata, Subscriber};
+    use log::Log;
+    use std::sync::{Arc, Mutex};
+
+    /// Records the level, target, and formatted message of every `Event` it
+    /// observes, so a test can check what `LogTracer::log` handed to the
+    /// dispatcher without caring how it got there.
+    #[derive(Clone, Default)]
+    struct RecordingSubscriber(Arc<Mutex<Vec<(Level, String, String)>>>);
+
+    impl RecordingSubscriber {
+        fn events(&self) -> Vec<(Level, String, String)> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn new_span(&self, _metadata: &Metadata<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+        fn record_debug(&self, _span: &span::Id, _field: &Field, _value: &dyn std::fmt::Debug) {}
+        fn add_follows_from(&self, _span: &span::Id, _follows: span::Id) {}
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+        fn enter(&self, _span: &span::Id) {}
+        fn exit(&self, _span: &span::Id) {}
+        fn observe_event(&self, event: &crate::Event<'_>) {
+            let metadata = event.metadata();
+            self.0.lock().unwrap().push((
+                *metadata.level(),
+                metadata.target().to_string(),
+                event.message().to_string(),
+            ));
+        }
+    }
+
+    fn record(level: log::Level, target: &str, args: std::fmt::Arguments<'_>) -> log::Record<'static> {
+        log::Record::builder()
+            .level(level)
+            .target(target)
+            .args(args)
+            .build()
+    }
+
+    #[test]
+    fn log_records_are_observed_as_events_without_panicking() {
+        let subscriber = RecordingSubscriber::default();
+        crate::subscriber::with_default(subscriber.clone(), || {
+            let tracer = LogTracer::new();
+
+            tracer.log(&record(log::Level::Info, "some_target", format_args!("hello")));
+            tracer.log(&record(log::Level::Error, "other_target", format_args!("boom: {}", 42)));
+            tracer.log(&record(log::Level::Trace, "some_target", format_args!("tracing")));
+        });
+
+        let events = subscriber.events();
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].0, Level::INFO);
+        assert_eq!(events[0].1, "some_target");
+        assert_eq!(events[0].2, "hello");
+
+        assert_eq!(events[1].0, Level::ERROR);
+        assert_eq!(events[1].1, "other_target");
+        assert_eq!(events[1].2, "boom: 42");
+
+        assert_eq!(events[2].0, Level::TRACE);
+        assert_eq!(events[2].1, "some_target");
+        assert_eq!(events[2].2, "tracing");
+    }
+}